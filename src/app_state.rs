@@ -1,5 +1,7 @@
 use std::cmp::min;
-use std::iter::zip;
+use std::mem;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crossterm::event::Event;
 use ratatui::widgets::*;
@@ -7,10 +9,22 @@ use tui_input::backend::crossterm::EventHandler;
 use tui_input::Input;
 
 use crate::app_state::SearchState::{BrowsingSearch, NotSearching, Searching};
-use crate::json_item::{JsonItem, JsonValueType};
-use crate::search::update_search_results;
+use crate::fenwick_tree::FenwickTree;
+use crate::fuzzy_finder::FuzzyFinder;
+use crate::jq_filter::{BackgroundFilter, DocumentValue};
+use crate::json_item::{DisplayState, JsonItem, JsonValueType};
+use crate::lazy_json::LazyDocument;
+use crate::load::Document;
+use crate::results_panel::ResultsPanel;
+use crate::search::{BackgroundSearch, Query, SearchOptions, SearchResult};
+use crate::search_history::SearchHistory;
+use crate::tree;
 use thousands::Separable;
 
+/// How long to wait for a new search or filter before showing partial results, so that
+/// small documents don't flicker while typing
+const SEARCH_WAIT: Duration = Duration::from_millis(20);
+
 #[derive(PartialEq)]
 pub enum SearchState {
     NotSearching,
@@ -18,72 +32,260 @@ pub enum SearchState {
     BrowsingSearch(Option<usize>),
 }
 
+/// A document shown in the main list, together with where the user was in it
+struct View {
+    items: Arc<Vec<JsonItem>>,
+    collapsed: Vec<bool>,
+    selection: usize,
+    top_index: usize,
+}
+
+impl View {
+    fn new(items: Vec<JsonItem>) -> View {
+        View {
+            // Lazily loaded containers start out collapsed
+            collapsed: items
+                .iter()
+                .map(|item| item.unloaded_span.is_some())
+                .collect(),
+            items: Arc::new(items),
+            selection: 0,
+            top_index: 0,
+        }
+    }
+}
+
 pub struct AppState {
     pub list_state: ListState,
-    pub items: Vec<JsonItem>,
-    pub visible_items: Vec<JsonItem>,
+    /// Shared with background searches; only changed when lazily loaded items are added
+    /// or the output of a filter is shown
+    pub items: Arc<Vec<JsonItem>>,
+    // View state of the items, by line number
+    collapsed: Vec<bool>,
+    visible: Vec<bool>,
+    /// The `visible` flags, for going between line numbers and positions
+    visible_lines: FenwickTree,
+    /// Only show the search results, their contents and their ancestors
+    matches_only: bool,
+    /// The lines that can be visible when showing matches only
+    kept: Option<Vec<bool>>,
     pub filename: String,
     pub list_height: u16,
     pub search_state: SearchState,
     pub search_input: Input,
+    pub search_options: SearchOptions,
+    pub search_history: SearchHistory,
+    pub results_panel: ResultsPanel,
     num_items_in_file: usize,
     top_index: usize,
+    /// Matching items in document order
+    search_results: Vec<SearchResult>,
+    /// The search that is still looking for more results
+    search: Option<BackgroundSearch>,
+    /// Why the search text can't be searched for, e.g. an invalid regular expression
+    search_error: Option<String>,
+    lazy_document: Option<Arc<LazyDocument>>,
+    pub editing_filter: bool,
+    pub filter_input: Input,
+    /// The filter that is still computing its output
+    filter: Option<BackgroundFilter>,
+    filter_error: Option<String>,
+    /// The original document, converted once for filtering
+    filter_input_value: Option<DocumentValue>,
+    /// Whether the user agreed to read all of a lazily loaded file for filtering
+    filter_reads_file: bool,
+    /// The original document while the output of the filter is shown
+    original: Option<View>,
+    /// The output of the filter after toggling back to the original
+    filter_output: Option<View>,
+    pub fuzzy_finder: Option<FuzzyFinder>,
 }
-// list_state.selected: index into visible_items
+// list_state.selected: position among the visible items
 // self.selection_index(): index into items
 
 impl AppState {
-    pub fn new(items: Vec<JsonItem>, filename: String) -> AppState {
+    pub fn new(document: Document) -> AppState {
+        let Document {
+            items,
+            filename,
+            lazy_document,
+        } = document;
+        let View {
+            items, collapsed, ..
+        } = View::new(items);
         let mut app_state = AppState {
             list_state: ListState::default(),
-            items: items.clone(),
-            visible_items: items.clone(),
+            collapsed,
+            visible: vec![],
+            visible_lines: FenwickTree::from_flags([].into_iter()),
+            matches_only: false,
+            kept: None,
+            items,
             filename,
             list_height: 0,
             search_state: NotSearching,
             search_input: Input::new("".to_string()),
+            search_options: SearchOptions::default(),
+            search_history: SearchHistory::load(),
+            results_panel: ResultsPanel::new(),
             num_items_in_file: 0,
             top_index: 0,
+            search_results: vec![],
+            search: None,
+            search_error: None,
+            lazy_document: lazy_document.map(Arc::new),
+            editing_filter: false,
+            filter_input: Input::new("".to_string()),
+            filter: None,
+            filter_error: None,
+            filter_input_value: None,
+            filter_reads_file: false,
+            original: None,
+            filter_output: None,
+            fuzzy_finder: None,
         };
-        let values: Vec<&JsonItem> = items
-            .iter()
-            .filter(|i| i.value != JsonValueType::ObjectEnd && i.value != JsonValueType::ArrayEnd)
-            .collect();
-        app_state.num_items_in_file = values.len();
+        app_state.count_values();
+        app_state.recalculate_visible();
         app_state.select_next(1);
         app_state
     }
 
+    fn count_values(&mut self) {
+        let values: Vec<&JsonItem> = self
+            .items
+            .iter()
+            .filter(|i| {
+                !matches!(
+                    i.value,
+                    JsonValueType::ObjectEnd
+                        | JsonValueType::ArrayEnd
+                        | JsonValueType::Error(_)
+                        | JsonValueType::Comment(_)
+                        | JsonValueType::Unloaded(_)
+                )
+            })
+            .collect();
+        self.num_items_in_file = values.len();
+    }
+
     fn bottom_index(&self) -> usize {
         if self.list_height < 2 {
             1
         } else {
             let top = self.top_index as i32;
             let height = self.list_height as i32;
-            let num_visible_items = self.visible_items.len() as i32;
+            let num_visible_items = self.num_visible() as i32;
             min(top + height, num_visible_items) as usize
         }
     }
 
-    pub fn display_items(&self) -> Vec<JsonItem> {
-        self.visible_items[self.top_index..self.bottom_index()].to_vec()
+    /// The items on screen. Only the lines in view are looked at, so this doesn't get
+    /// slower with the size of the document.
+    pub fn display_items(&self) -> Vec<(&JsonItem, DisplayState)> {
+        let selection_index = self.selection_index();
+        // The indent guide of the container that holds the selection is highlighted
+        let container =
+            selection_index.and_then(|index| tree::enclosing_container(&self.items, index));
+        let count = self.bottom_index().saturating_sub(self.top_index);
+        self.visible_lines_from(self.line_at(self.top_index))
+            .take(count)
+            .map(|line| {
+                let search_result = self.search_result(line);
+                let state = DisplayState {
+                    collapsed: self.collapsed[line],
+                    selected: selection_index == Some(line),
+                    selection_level: container
+                        .filter(|container| tree::is_inside(&self.items, *container, line))
+                        .map(|container| self.items[container].indent),
+                    name_is_search_result: search_result
+                        .is_some_and(|result| result.name_is_search_result),
+                    value_is_search_result: search_result
+                        .is_some_and(|result| result.value_is_search_result),
+                };
+                (&self.items[line], state)
+            })
+            .collect()
+    }
+
+    /// Matching items in document order
+    pub fn search_results(&self) -> &[SearchResult] {
+        &self.search_results
+    }
+
+    fn search_result(&self, line_number: usize) -> Option<&SearchResult> {
+        self.search_results
+            .binary_search_by_key(&line_number, |result| result.line_number)
+            .ok()
+            .map(|index| &self.search_results[index])
+    }
+
+    fn num_visible(&self) -> usize {
+        self.visible_lines.count()
+    }
+
+    /// Line number of the item at the given position among the visible items
+    fn line_at(&self, position: usize) -> usize {
+        self.visible_lines.find_nth(position).unwrap_or(0)
+    }
+
+    /// Position of a visible item among the visible items
+    fn position_of(&self, line_number: usize) -> usize {
+        self.visible_lines.count_before(line_number)
+    }
+
+    /// Line numbers of the visible items, starting at the visible item `line_number`
+    fn visible_lines_from(&self, line_number: usize) -> impl Iterator<Item = usize> + '_ {
+        (self.position_of(line_number)..self.num_visible()).map(|position| self.line_at(position))
+    }
+
+    /// The file name, followed by the filter when its output is shown
+    pub fn title(&self) -> String {
+        let title = match self.original {
+            Some(_) => format!("{} | {}", self.filename, self.filter_input.value()),
+            None => self.filename.clone(),
+        };
+        match self.matches_only {
+            true => format!("{} (matches only)", title),
+            false => title,
+        }
     }
 
     pub fn status_text(&self) -> String {
+        if self.editing_filter {
+            if let Some(error) = &self.filter_error {
+                return error.clone();
+            }
+            if self.filter.is_some() {
+                return "Filtering…".to_string();
+            }
+        }
         match self.search_state {
-            Searching => {
-                let num_results = self.search_results().len();
-                format!("{} results", num_results)
+            Searching if self.search_error.is_some() => self.search_error.clone().unwrap(),
+            Searching | BrowsingSearch(None) if self.search.is_some() => {
+                format!(
+                    "{} results so far…",
+                    self.search_results.len().separate_with_commas()
+                )
             }
+            Searching => format!(
+                "{} results",
+                self.search_results.len().separate_with_commas()
+            ),
             BrowsingSearch(Some(index)) => {
-                let num_results = self.search_results().len();
-                format!("Result {} of {}", index + 1, num_results)
+                format!("Result {} of {}", index + 1, self.search_results.len())
             }
             _ => {
-                let f = self.selection_index().unwrap_or(0) as f32 / (self.items.len() - 1) as f32;
+                let f = self.selection_index().unwrap_or(0) as f32
+                    / (self.items.len() - 1).max(1) as f32;
+                let values = match (&self.original, &self.lazy_document) {
+                    (Some(_), _) => "values in output",
+                    (None, Some(_)) => "values loaded",
+                    (None, None) => "values in file",
+                };
                 format!(
-                    " {} values in file | {:.0} %",
+                    " {} {} | {:.0} %",
                     self.num_items_in_file.separate_with_spaces(),
+                    values,
                     f * 100.0
                 )
             }
@@ -92,7 +294,7 @@ impl AppState {
 
     pub fn breadbrumbs_text(&self) -> String {
         match self.selection_index() {
-            Some(index) => self.items[index].breadcrumbs.clone(),
+            Some(index) => tree::breadcrumbs(&self.items, index),
             None => "".to_string(),
         }
     }
@@ -100,67 +302,111 @@ impl AppState {
     pub fn select_next(&mut self, step: usize) {
         let new_index = match self.list_state.selected() {
             None => 0,
-            Some(index) => min(index + step, self.visible_items.len() - 1),
+            Some(index) => min(index + step, self.num_visible() - 1),
         };
         self.select_index(new_index);
     }
 
     pub fn select_next_object(&mut self) {
-        let new_index = match self.list_state.selected() {
-            None => Some(0),
-            Some(selection_index) => {
-                let indent = match self.visible_items[selection_index].value {
-                    JsonValueType::Array
-                    | JsonValueType::ArrayEnd
-                    | JsonValueType::Object
-                    | JsonValueType::ObjectEnd => self.visible_items[selection_index].indent,
-                    _ => self.visible_items[selection_index].indent - 1,
-                };
-                self.visible_items
-                    .iter()
-                    .enumerate()
-                    .find(|(index, item)| index > &selection_index && item.indent == indent)
-                    .map(|(index, _item)| index)
+        if let Some(selection_index) = self.selection_index() {
+            if let Some(line) = self.next_at_indent(selection_index) {
+                self.select_line(line);
             }
         }
-        .unwrap_or(self.list_state.selected().unwrap_or(0));
-        self.select_index(new_index);
     }
 
     pub fn select_previous(&mut self, step: usize) {
         let new_index = match self.list_state.selected() {
             None => 0,
-            Some(index) => {
-                if index > step {
-                    index - step
-                } else {
-                    0
-                }
-            }
+            Some(index) => index.saturating_sub(step),
         };
         self.select_index(new_index);
     }
 
     pub fn select_previous_object(&mut self) {
-        let new_index = match self.list_state.selected() {
-            None => Some(0),
-            Some(selection_index) => {
-                let indent = match self.visible_items[selection_index].value {
-                    JsonValueType::Array
-                    | JsonValueType::ArrayEnd
-                    | JsonValueType::Object
-                    | JsonValueType::ObjectEnd => self.visible_items[selection_index].indent,
-                    _ => self.visible_items[selection_index].indent - 1,
+        if let Some(selection_index) = self.selection_index() {
+            if let Some(line) = self.previous_at_indent(selection_index) {
+                self.select_line(line);
+            }
+        }
+    }
+
+    /// The first visible item after `line_number` at the indent that J moves along.
+    /// Containers deeper than that are stepped over using their brackets, so this only
+    /// looks at the items around the path between the two.
+    fn next_at_indent(&self, line_number: usize) -> Option<usize> {
+        let indent = self.object_indent(line_number);
+        let mut line = self.after_subtree(line_number);
+        while line < self.items.len() {
+            let item = &self.items[line];
+            if item.indent > indent {
+                // Inside a container at that indent, whose closing bracket comes next
+                line = self.items[self.ancestor_at_indent(line, indent)?].bracket?;
+            } else if item.indent == indent && self.visible[line] {
+                return Some(line);
+            } else {
+                line = self.after_subtree(line);
+            }
+        }
+        None
+    }
+
+    /// Like `next_at_indent`, for K
+    fn previous_at_indent(&self, line_number: usize) -> Option<usize> {
+        let indent = self.object_indent(line_number);
+        let mut line = line_number.checked_sub(1)?;
+        loop {
+            let item = &self.items[line];
+            if item.indent > indent {
+                // Inside a container at that indent, whose opening bracket comes next
+                line = self.ancestor_at_indent(line, indent)?;
+            } else if item.indent == indent && self.visible[line] {
+                return Some(line);
+            } else {
+                // Step over the members of containers that are collapsed or hidden
+                line = match item.value {
+                    JsonValueType::ArrayEnd | JsonValueType::ObjectEnd if !self.visible[line] => {
+                        item.bracket?
+                    }
+                    _ => line.checked_sub(1)?,
                 };
-                self.visible_items
-                    .iter()
-                    .enumerate()
-                    .rfind(|(index, item)| index < &selection_index && item.indent == indent)
-                    .map(|(index, _item)| index)
             }
         }
-        .unwrap_or(self.list_state.selected().unwrap_or(0));
-        self.select_index(new_index);
+    }
+
+    /// The line after an item, skipping the members of a collapsed or hidden container
+    fn after_subtree(&self, line_number: usize) -> usize {
+        let item = &self.items[line_number];
+        match item.value {
+            JsonValueType::Array | JsonValueType::Object
+                if self.collapsed[line_number] || !self.visible[line_number] =>
+            {
+                item.bracket.unwrap_or(line_number) + 1
+            }
+            _ => line_number + 1,
+        }
+    }
+
+    /// The container with the given indent that holds an item
+    fn ancestor_at_indent(&self, line_number: usize, indent: usize) -> Option<usize> {
+        let mut ancestor = tree::enclosing_container(&self.items, line_number)?;
+        while self.items[ancestor].indent > indent {
+            ancestor = self.items[ancestor].parent?;
+        }
+        Some(ancestor)
+    }
+
+    /// Indent of the items that J and K move between: the container's own level for
+    /// brackets, and the enclosing container's level for values
+    fn object_indent(&self, line_number: usize) -> usize {
+        let item = &self.items[line_number];
+        match item.value {
+            JsonValueType::Array
+            | JsonValueType::ArrayEnd
+            | JsonValueType::Object
+            | JsonValueType::ObjectEnd => item.indent,
+            _ => item.indent.saturating_sub(1),
+        }
     }
 
     pub fn select_top(&mut self) {
@@ -168,7 +414,7 @@ impl AppState {
     }
 
     pub fn select_bottom(&mut self) {
-        self.select_index(self.visible_items.len() - 1);
+        self.select_index(self.num_visible() - 1);
     }
 
     pub fn select_top_of_screen(&mut self) {
@@ -177,7 +423,7 @@ impl AppState {
 
     pub fn select_middle_of_screen(&mut self) {
         let top = self.top_index as u16;
-        let num_items = self.visible_items.len() as u16;
+        let num_items = self.num_visible() as u16;
         let bottom = min(top + num_items - 1, top + self.list_height - 2);
         let index = (top + bottom) / 2;
         self.select_index((index) as usize);
@@ -191,54 +437,154 @@ impl AppState {
     }
 
     pub fn toggle_collapsed(&mut self) {
-        if let Some(index) = self.selection_index() {
-            {
-                let mut i = index;
-                loop {
-                    match &self.items[i].value {
-                        JsonValueType::Array | JsonValueType::Object => {
-                            self.items[i].collapsed = !self.items[i].collapsed;
-                            if let Some(selection) = self.list_state.selected() {
-                                let diff = index - i;
-                                self.select_index(selection - diff);
-                            }
-                            self.recalculate_visible();
-                            self.recalculate_selection_level();
-                            break;
-                        }
-                        _ => {
-                            if i == 0 {
-                                break;
-                            }
-                            i -= 1;
-                        }
-                    }
-                }
+        let container = self
+            .selection_index()
+            .and_then(|index| tree::enclosing_container(&self.items, index));
+        if let Some(container) = container {
+            self.load_children(container);
+            self.set_collapsed(container, !self.collapsed[container]);
+            self.select_line(container);
+        }
+    }
+
+    /// Parse the children of a lazily loaded container and insert them after it
+    fn load_children(&mut self, index: usize) {
+        let lazy_document = match &self.lazy_document {
+            Some(lazy_document) if self.items[index].unloaded_span.is_some() => lazy_document,
+            _ => return,
+        };
+        let children = lazy_document.load_children(&self.items[index]);
+        let (start, num_children) = (index + 1, children.len());
+        self.splice_items(start, 0, children);
+        let items = Arc::make_mut(&mut self.items);
+        let parent = &mut items[index];
+        parent.unloaded_span = None;
+        if num_children > 0 {
+            parent.first_child = Some(start);
+        }
+        self.after_splice();
+    }
+
+    /// Read the members of lazily loaded containers whose placeholders are on screen
+    pub fn load_items_in_view(&mut self) {
+        // A page is longer than the screen, so each load moves the placeholder out of view
+        loop {
+            let count = self.bottom_index().saturating_sub(self.top_index);
+            let placeholder = self
+                .visible_lines_from(self.line_at(self.top_index))
+                .take(count)
+                .find(|line| matches!(self.items[*line].value, JsonValueType::Unloaded(_)));
+            match placeholder {
+                Some(placeholder) => self.load_members(placeholder),
+                None => return,
             }
         }
     }
 
+    /// Replace the placeholder of unloaded members by the page next to what is selected,
+    /// keeping the selected item where it is on screen
+    fn load_members(&mut self, placeholder: usize) {
+        let (lazy_document, parent) = match (&self.lazy_document, self.items[placeholder].parent) {
+            (Some(lazy_document), Some(parent)) => (lazy_document, parent),
+            _ => return,
+        };
+        let selection = self.list_state.selected().unwrap_or(0);
+        let selected_line = self.selection_index().unwrap_or(0);
+        // Coming from below, load the members just above the selection. A placeholder that
+        // is itself selected was scrolled to from the edge of the screen it is on.
+        let position = self.position_of(placeholder);
+        let from_end = position < selection
+            || (position == selection
+                && (selection - self.top_index) * 2 < self.list_height as usize);
+        let members =
+            lazy_document.load_members(&self.items[parent], &self.items[placeholder], from_end);
+        if members.is_empty() {
+            return;
+        }
+        let num_added = members.len() - 1;
+        let num_visible = self.num_visible();
+        let next_sibling = self.items[placeholder].next_sibling;
+        self.splice_items(placeholder, 1, members);
+        // The last of the new members continues the chain of siblings
+        let items = Arc::make_mut(&mut self.items);
+        let last_member = (placeholder..=placeholder + num_added).rev().find(|line| {
+            items[*line].parent == Some(parent)
+                && !matches!(
+                    items[*line].value,
+                    JsonValueType::ArrayEnd | JsonValueType::ObjectEnd
+                )
+        });
+        if let Some(last_member) = last_member {
+            items[last_member].next_sibling = next_sibling.map(|line| line + num_added);
+        }
+        self.after_splice();
+        if from_end {
+            self.top_index += self.num_visible() - num_visible;
+        }
+        if selected_line > placeholder {
+            self.select_line(selected_line + num_added);
+        } else if let (true, Some(last_member)) =
+            (selected_line == placeholder && from_end, last_member)
+        {
+            self.select_line(last_member);
+        } else {
+            self.select_index(selection);
+        }
+    }
+
+    /// Replace `removed` items at `start` by `new_items`, whose line numbers and links are
+    /// already those of their new place, and move everything after them
+    fn splice_items(&mut self, start: usize, removed: usize, new_items: Vec<JsonItem>) {
+        let num_added = new_items.len() - removed;
+        let collapsed: Vec<bool> = new_items
+            .iter()
+            .map(|item| item.unloaded_span.is_some())
+            .collect();
+
+        // Only copies the items if a background search is still using them
+        let items = Arc::make_mut(&mut self.items);
+        for item in items.iter_mut() {
+            item.shift_links(start + removed, num_added);
+        }
+        items.splice(start..start + removed, new_items);
+        for (line_number, item) in items.iter_mut().enumerate().skip(start) {
+            item.line_number = line_number;
+        }
+        self.collapsed.splice(start..start + removed, collapsed);
+        self.search_results
+            .retain(|result| !(start..start + removed).contains(&result.line_number));
+        for result in self.search_results.iter_mut() {
+            if result.line_number >= start {
+                result.line_number += num_added;
+            }
+        }
+    }
+
+    fn after_splice(&mut self) {
+        // A search that is still running reports line numbers from before the insertion
+        if self.search.is_some() {
+            self.start_search();
+        }
+        self.count_values();
+        self.recalculate_visible();
+    }
+
     pub fn collapse_level(&mut self) {
         if let Some(index) = self.selection_index() {
             match &self.items[index].value {
                 JsonValueType::Array | JsonValueType::Object => {
                     let indent = self.items[index].indent;
                     let line_number = self.items[index].line_number;
-                    for item in self.items.iter_mut() {
+                    for (item, collapsed) in self.items.iter().zip(self.collapsed.iter_mut()) {
                         if item.indent >= indent
                             && (item.value == JsonValueType::Array
                                 || item.value == JsonValueType::Object)
                         {
-                            item.collapsed = true;
+                            *collapsed = true;
                         }
                     }
                     self.recalculate_visible();
-                    self.select_index(
-                        self.visible_items
-                            .iter()
-                            .position(|item| item.line_number == line_number)
-                            .unwrap_or(0),
-                    );
+                    self.select_line(line_number);
                 }
                 _ => {}
             }
@@ -246,89 +592,193 @@ impl AppState {
     }
 
     pub fn uncollapse_all(&mut self) {
-        let line_number = self.visible_items[self.list_state.selected().unwrap_or(0)].line_number;
-        for item in self.items.iter_mut() {
-            item.collapsed = false;
-            item.visible = true;
-        }
-        self.visible_items = self.items.clone();
-        self.select_index(
-            self.visible_items
-                .iter()
-                .position(|item| item.line_number == line_number)
-                .unwrap_or(0),
-        );
+        let line_number = self.selection_index().unwrap_or(0);
+        for (item, collapsed) in self.items.iter().zip(self.collapsed.iter_mut()) {
+            // Expanding everything would parse the whole of a lazily loaded file
+            *collapsed = item.unloaded_span.is_some();
+        }
+        self.recalculate_visible();
+        self.select_line(line_number);
     }
 
     pub fn selection_index(&self) -> Option<usize> {
         self.list_state
             .selected()
-            .map(|index| self.visible_items[index].line_number)
+            .map(|position| self.line_at(position))
     }
 
+    /// Collapse or expand a container, updating only the items inside it
+    fn set_collapsed(&mut self, container: usize, collapsed: bool) {
+        self.collapsed[container] = collapsed;
+        let end = match self.items[container].bracket {
+            Some(end) if self.visible[container] => end,
+            _ => return,
+        };
+        let mut line = container + 1;
+        while line <= end {
+            let kept = self.kept.as_ref().is_none_or(|kept| kept[line]);
+            self.set_visible(line, !collapsed && kept);
+            // Nested collapsed containers keep their contents hidden either way, and
+            // nothing inside a container that isn't kept is kept either
+            line = match self.collapsed[line] || !kept {
+                true => self.items[line].bracket.unwrap_or(line) + 1,
+                false => line + 1,
+            };
+        }
+    }
+
+    fn set_visible(&mut self, line_number: usize, visible: bool) {
+        if self.visible[line_number] != visible {
+            self.visible[line_number] = visible;
+            match visible {
+                true => self.visible_lines.set(line_number),
+                false => self.visible_lines.clear(line_number),
+            }
+        }
+    }
+
+    /// Recompute the visibility of all items, after changes to the whole document
     fn recalculate_visible(&mut self) {
-        // TODO: optimize
-        let mut is_in_collapsed = false;
-        let mut collapse_indent = 0;
-        for item in self.items.iter_mut() {
-            item.visible = true;
-            if is_in_collapsed {
-                if item.indent == collapse_indent {
-                    is_in_collapsed = false;
-                    item.visible = false; // closing bracket
-                    continue;
+        let kept = self.matches_only.then(|| self.kept_lines());
+        let mut hidden_until = None;
+        self.visible = (self.items.iter().enumerate())
+            .map(|(index, item)| match hidden_until {
+                Some(end) if index <= end => false,
+                _ if kept.as_ref().is_some_and(|kept| !kept[index]) => false,
+                _ => {
+                    hidden_until = item.bracket.filter(|_| self.collapsed[index]);
+                    true
+                }
+            })
+            .collect();
+        self.visible_lines = FenwickTree::from_flags(self.visible.iter().copied());
+        self.kept = kept;
+    }
+
+    /// Lines shown when showing matches only: the search results with everything inside
+    /// them, their ancestors and the closing brackets of all of these. The root is always
+    /// kept, so that there is something to show.
+    fn kept_lines(&self) -> Vec<bool> {
+        let mut kept = vec![false; self.items.len()];
+        // The root stays, not a comment before it
+        if let Some(root) = tree::root(&self.items) {
+            kept[root] = true;
+            if let Some(bracket) = self.items[root].bracket {
+                kept[bracket] = true;
+            }
+        }
+        self.keep_results(&mut kept, &self.search_results, None);
+        kept
+    }
+
+    /// Keep `results`, their contents and their ancestors. Returns the lines that weren't
+    /// kept before, in document order.
+    fn keep_results(
+        &self,
+        kept: &mut [bool],
+        results: &[SearchResult],
+        mut kept_until: Option<usize>,
+    ) -> Vec<usize> {
+        let mut newly_kept = vec![];
+        let mut keep = |kept: &mut [bool], line: usize| {
+            if !kept[line] {
+                kept[line] = true;
+                newly_kept.push(line);
+            }
+        };
+        for result in results {
+            let line = result.line_number;
+            if kept_until.is_some_and(|end| line <= end) {
+                // Inside a result that is kept as a whole
+                continue;
+            }
+            let end = self.result_end(line);
+            for line in line..=end {
+                keep(kept, line);
+            }
+            kept_until = Some(end);
+            // Ancestors of a kept item are kept already
+            let mut parent = self.items[line].parent;
+            while let Some(line) = parent.filter(|parent| !kept[*parent]) {
+                keep(kept, line);
+                if let Some(bracket) = self.items[line].bracket {
+                    keep(kept, bracket);
                 }
-                item.visible = false;
-            } else if item.collapsed {
-                is_in_collapsed = true;
-                collapse_indent = item.indent;
+                parent = self.items[line].parent;
             }
         }
-        self.visible_items = self.items.iter().filter(|i| i.visible).cloned().collect();
+        newly_kept.sort_unstable();
+        newly_kept
     }
 
-    fn recalculate_selection_level(&mut self) {
-        if let Some(index) = self.selection_index() {
-            // For non-containers, strip away the last component of the breadcrumbs
-            let selection_breadcrumbs = match self.items[index].value {
-                JsonValueType::Number(_)
-                | JsonValueType::Bool(_)
-                | JsonValueType::String(_)
-                | JsonValueType::Null => match self.items[index].breadcrumbs.rsplit_once(" ▶ ") {
-                    Some((val, _)) => val.to_string(),
-                    None => "".to_string(),
-                },
-                _ => self.items[index].breadcrumbs.clone(),
+    /// The last line of a result, which is kept with all its contents
+    fn result_end(&self, line: usize) -> usize {
+        match self.items[line].value {
+            JsonValueType::Array | JsonValueType::Object => {
+                self.items[line].bracket.unwrap_or(line)
+            }
+            _ => line,
+        }
+    }
+
+    /// Show the items of new search results when showing matches only, without going over
+    /// the whole document again. Results come in document order, after the earlier ones.
+    fn show_new_search_results(&mut self, first_new: usize) {
+        let mut kept = match self.kept.take() {
+            Some(kept) if self.matches_only => kept,
+            kept => {
+                self.kept = kept;
+                return;
+            }
+        };
+        let line_number = self.selection_index().unwrap_or(0);
+        let kept_until = first_new
+            .checked_sub(1)
+            .map(|last| self.result_end(self.search_results[last].line_number));
+        let new_results = &self.search_results[first_new..];
+        for line in self.keep_results(&mut kept, new_results, kept_until) {
+            // Containers come before their contents, so theirs is known already
+            let item = &self.items[line];
+            let container = match item.value {
+                JsonValueType::ArrayEnd | JsonValueType::ObjectEnd => item.bracket,
+                _ => item.parent,
             };
-            let mut selection_level: usize;
-            let bottom = self.bottom_index();
-            // Loop through all items and calculate selection level
-            for item in self.visible_items[self.top_index..bottom].iter_mut() {
-                if item.breadcrumbs.starts_with(&selection_breadcrumbs) {
-                    selection_level = 0;
-                    // How many components of the breadcrumbs match?
-                    for (p1, p2) in zip(
-                        selection_breadcrumbs.split(" ▶ "),
-                        item.breadcrumbs.split(" ▶ "),
-                    ) {
-                        if p1 == p2 {
-                            selection_level += 1;
-                        } else {
-                            break;
-                        }
-                    }
-                    item.selection_level = Some(selection_level);
-                } else {
-                    item.selection_level = None;
-                }
+            if container
+                .is_none_or(|container| self.visible[container] && !self.collapsed[container])
+            {
+                self.visible[line] = true;
+                self.visible_lines.set(line);
             }
         }
+        self.kept = Some(kept);
+        let position = self.position_of(line_number);
+        self.select_index(position.min(self.num_visible() - 1));
+    }
+
+    pub fn toggle_matches_only(&mut self) {
+        self.matches_only = !self.matches_only;
+        self.update_matches_only();
+    }
+
+    /// Update the visible items after the search results changed, when showing matches only
+    fn update_matches_only(&mut self) {
+        if self.matches_only || self.kept.is_some() {
+            let line_number = self.selection_index().unwrap_or(0);
+            self.recalculate_visible();
+            // Stay at the same place in the document, or the next item that is still visible
+            let position = self.position_of(line_number);
+            self.select_index(position.min(self.num_visible() - 1));
+        }
+    }
+
+    /// Select the visible item with the given line number
+    fn select_line(&mut self, line_number: usize) {
+        self.select_index(self.position_of(line_number));
     }
 
     fn select_index(&mut self, index: usize) {
         self.list_state.select(Some(index));
         self.recalculate_scroll_position();
-        self.recalculate_selection_level();
     }
 
     fn recalculate_scroll_position(&mut self) {
@@ -347,84 +797,709 @@ impl AppState {
     }
 
     pub fn start_searching(&mut self) {
-        self.uncollapse_all();
         self.search_state = Searching;
         self.search_input = Input::new("".to_string());
-        self.update_search_results();
+        self.start_search();
     }
 
     pub fn start_searching_for_name(&mut self) {
         if let Some(index) = self.selection_index() {
-            if let Some(name) = self.visible_items[index].name.clone() {
+            if let Some(name) = self.items[index].name.clone() {
+                self.search_state = Searching;
                 self.search_input = self.search_input.clone().with_value(name);
-                self.start_searching();
+                self.start_search();
                 self.finish_searching();
             }
         }
     }
 
+    /// Search for the JSONPath of the selected item, to edit it into a path to similar
+    /// items, e.g. `$.items[*].name`
+    pub fn start_searching_for_path(&mut self) {
+        if let Some(index) = self.selection_index() {
+            self.search_state = Searching;
+            let path = tree::json_path(&self.items, index);
+            self.search_input = self.search_input.clone().with_value(path);
+            self.start_search();
+        }
+    }
+
     pub fn cancel_searching(&mut self) {
+        self.search_history.stop_browsing();
         self.search_state = NotSearching;
-        self.update_search_results();
     }
+
     pub fn finish_searching(&mut self) {
-        self.update_search_results();
-        self.search_state = match self.search_results().first() {
-            Some(_) => BrowsingSearch(Some(0)),
-            None => NotSearching,
+        self.search_history.add(self.search_input.value());
+        self.search_state = match (self.search_results.is_empty(), &self.search) {
+            (false, _) => BrowsingSearch(Some(self.select_search_result(0))),
+            // The first result is selected when it comes in
+            (true, Some(_)) => BrowsingSearch(None),
+            (true, None) => NotSearching,
         };
     }
 
-    fn search_results(&self) -> Vec<usize> {
-        self.visible_items
-            .iter()
-            .enumerate()
-            .filter(|(_index, item)| item.name_is_search_result || item.value_is_search_result)
-            .map(|(index, _item)| index)
-            .collect()
+    pub fn update_search(&mut self, event: &Event) {
+        if let Some(change) = self.search_input.handle_event(event) {
+            if change.value {
+                self.search_history.stop_browsing();
+                self.start_search();
+            }
+        }
     }
 
-    pub fn update_search(&mut self, event: &Event) {
-        let is_large_file = self.num_items_in_file > 1_00_000;
-        self.search_input.handle_event(event);
-        if !is_large_file {
-            self.update_search_results();
+    /// Start searching for the search text in the background, cancelling the previous search
+    fn start_search(&mut self) {
+        self.search_results.clear();
+        self.results_panel.selected = 0;
+        self.search = None;
+        if let BrowsingSearch(Some(_)) = self.search_state {
+            // The chosen result is gone, so the first new one is chosen when it comes in
+            self.search_state = BrowsingSearch(None);
+        }
+        self.update_matches_only();
+        match Query::parse(self.search_input.value(), self.search_options) {
+            Ok(query) => {
+                self.search_error = None;
+                self.search = Some(BackgroundSearch::start(self.items.clone(), query));
+                self.receive_search_results(SEARCH_WAIT);
+            }
+            Err(error) => self.search_error = Some(error),
+        }
+    }
+
+    /// Replace the search text with an older one from the history
+    pub fn recall_previous_search(&mut self) {
+        let current = self.search_input.value().to_string();
+        let recalled = self.search_history.previous(&current);
+        self.recall_search(recalled);
+    }
+
+    /// Replace the search text with a newer one from the history, or what was typed
+    pub fn recall_next_search(&mut self) {
+        let recalled = self.search_history.next();
+        self.recall_search(recalled);
+    }
+
+    /// Replace the search text with an older one containing what was typed
+    pub fn recall_matching_search(&mut self) {
+        let current = self.search_input.value().to_string();
+        let recalled = self.search_history.search(&current);
+        self.recall_search(recalled);
+    }
+
+    fn recall_search(&mut self, search_string: Option<String>) {
+        if let Some(search_string) = search_string {
+            self.search_input = self.search_input.clone().with_value(search_string);
+            self.start_search();
         }
     }
 
-    fn update_search_results(&mut self) {
-        update_search_results(self.visible_items.iter_mut(), self.search_input.value());
-        if self.search_state == Searching {
-            let search_results = self.search_results();
-            if !search_results.is_empty() {
-                let new_index = 0;
-                self.select_index(search_results[new_index]);
+    /// Change the search options and search again with them
+    pub fn toggle_search_option(&mut self, toggle: impl FnOnce(&mut SearchOptions)) {
+        toggle(&mut self.search_options);
+        self.start_search();
+    }
+
+    pub fn is_search_running(&self) -> bool {
+        self.search.is_some()
+    }
+
+    /// Take the results that the background search has found since the last call
+    pub fn receive_search_results(&mut self, timeout: Duration) {
+        let search = match &self.search {
+            Some(search) => search,
+            None => return,
+        };
+        let num_results = self.search_results.len();
+        if !search.receive(&mut self.search_results, timeout) {
+            self.search = None;
+        }
+        if self.search_results.len() != num_results {
+            self.show_new_search_results(num_results);
+        }
+        let had_results = num_results > 0;
+        if !had_results && !self.search_results.is_empty() {
+            // Jump to the first result without waiting for the search to complete
+            match self.search_state {
+                // Collapsed containers are only expanded once a result is chosen
+                Searching => self.select_first_visible_search_result(),
+                BrowsingSearch(None) => {
+                    let index = self.select_search_result(0);
+                    self.search_state = BrowsingSearch(Some(index));
+                }
+                _ => {}
             }
         }
+        if self.search.is_none() && self.search_state == BrowsingSearch(None) {
+            self.search_state = NotSearching;
+        }
     }
 
-    pub fn search_text(&self) -> &str {
-        self.search_input.value()
+    /// Select the search result at `index`, expanding the containers around it. Returns
+    /// `index`.
+    fn select_search_result(&mut self, index: usize) -> usize {
+        let line_number = self.search_results[index].line_number;
+        self.expand_ancestors(line_number);
+        self.select_line(line_number);
+        self.results_panel.selected = index;
+        index
+    }
+
+    fn select_first_visible_search_result(&mut self) {
+        let first_visible = self
+            .search_results
+            .iter()
+            .map(|result| result.line_number)
+            .find(|line_number| self.visible[*line_number]);
+        if let Some(line_number) = first_visible {
+            self.select_line(line_number);
+        }
+    }
+
+    /// Expand the collapsed containers that hold an item, and nothing else
+    fn expand_ancestors(&mut self, line_number: usize) {
+        let mut ancestors = vec![];
+        let mut parent = self.items[line_number].parent;
+        while let Some(line_number) = parent {
+            ancestors.push(line_number);
+            parent = self.items[line_number].parent;
+        }
+        // Outermost first, since expanding only shows what's inside visible containers
+        for ancestor in ancestors.into_iter().rev() {
+            if self.collapsed[ancestor] {
+                self.set_collapsed(ancestor, false);
+            }
+        }
     }
 
     pub fn next_search_result(&mut self) {
+        if self.search_results.is_empty() {
+            return;
+        }
         if let BrowsingSearch(Some(index)) = self.search_state {
-            let search_results = self.search_results();
-            let new_index = (index + 1) % search_results.len();
-            self.select_index(search_results[new_index]);
+            let new_index = (index + 1) % self.search_results.len();
+            let new_index = self.select_search_result(new_index);
             self.search_state = BrowsingSearch(Some(new_index));
         }
     }
 
     pub fn previous_search_result(&mut self) {
+        if self.search_results.is_empty() {
+            return;
+        }
         if let BrowsingSearch(Some(index)) = self.search_state {
-            let search_results = self.search_results();
             let new_index = match index {
-                0 => search_results.len() - 1,
+                0 => self.search_results.len() - 1,
                 _ => index - 1,
             };
-            self.select_index(search_results[new_index]);
+            let new_index = self.select_search_result(new_index);
             self.search_state = BrowsingSearch(Some(new_index));
         }
     }
+
+    /// Show or hide the list of search results. Keys go to the list while it's shown.
+    pub fn toggle_results_panel(&mut self) {
+        self.results_panel.shown = !self.results_panel.shown;
+        self.results_panel.focused = self.results_panel.shown;
+    }
+
+    pub fn focus_results_panel(&mut self, focused: bool) {
+        self.results_panel.focused = focused && self.results_panel.shown;
+    }
+
+    pub fn select_next_in_results_panel(&mut self, step: usize) {
+        let index = self.results_panel.selected.saturating_add(step);
+        self.results_panel.select(index, self.search_results.len());
+    }
+
+    pub fn select_previous_in_results_panel(&mut self, step: usize) {
+        let index = self.results_panel.selected.saturating_sub(step);
+        self.results_panel.select(index, self.search_results.len());
+    }
+
+    /// Keep the selected result on screen. Called when drawing, since the panel's size
+    /// and the number of results change in between.
+    pub fn set_results_panel_rows(&mut self, rows: usize) {
+        let selected = self.results_panel.selected;
+        self.results_panel
+            .select(selected, self.search_results.len());
+        self.results_panel.scroll_to_selected(rows);
+    }
+
+    /// Go to the result selected in the results panel and give the keys back to the document
+    pub fn finish_results_panel(&mut self) {
+        if self.results_panel.selected < self.search_results.len() {
+            let index = self.select_search_result(self.results_panel.selected);
+            self.search_state = BrowsingSearch(Some(index));
+        }
+        self.results_panel.focused = false;
+    }
+
+    pub fn start_filtering(&mut self) {
+        self.search_state = NotSearching;
+        self.editing_filter = true;
+        if self.filter_output.is_some() {
+            self.toggle_filter_output();
+        }
+    }
+
+    /// Keep showing the output of the filter. While a lazily loaded file is waiting to be
+    /// read in full, this agrees to it instead.
+    pub fn finish_filtering(&mut self) {
+        if self.lazy_document.is_some()
+            && !self.filter_reads_file
+            && !self.filter_input.value().trim().is_empty()
+        {
+            self.filter_reads_file = true;
+            self.start_filter();
+            return;
+        }
+        self.editing_filter = false;
+    }
+
+    /// Go back to the original document. The filter text is kept for the next time.
+    pub fn cancel_filtering(&mut self) {
+        self.editing_filter = false;
+        self.filter = None;
+        self.filter_error = None;
+        self.show_original();
+    }
+
+    pub fn update_filter(&mut self, event: &Event) {
+        if let Some(change) = self.filter_input.handle_event(event) {
+            if change.value {
+                self.start_filter();
+            }
+        }
+    }
+
+    /// Run the filter text on the original document in the background, cancelling the
+    /// previous run. The current output stays on screen until the new one is ready.
+    fn start_filter(&mut self) {
+        self.filter = None;
+        self.filter_error = None;
+        if self.filter_input.value().trim().is_empty() {
+            self.show_original();
+            return;
+        }
+        if let (Some(lazy_document), false) = (&self.lazy_document, self.filter_reads_file) {
+            self.filter_error = Some(format!(
+                "Filtering reads all {} bytes of the file into memory. Press Enter to go ahead.",
+                lazy_document.size().separate_with_commas()
+            ));
+            return;
+        }
+        let original = match &self.original {
+            Some(view) => &view.items,
+            None => &self.items,
+        };
+        let input = self.filter_input_value.get_or_insert_with(|| {
+            DocumentValue::new(original.clone(), self.lazy_document.clone())
+        });
+        let code = self.filter_input.value().to_string();
+        self.filter = Some(BackgroundFilter::start(input.clone(), code));
+        self.receive_filter_output(SEARCH_WAIT);
+    }
+
+    pub fn is_filter_running(&self) -> bool {
+        self.filter.is_some()
+    }
+
+    /// Show the output of the filter once it's done
+    pub fn receive_filter_output(&mut self, timeout: Duration) {
+        let output = match &self.filter {
+            Some(filter) => filter.receive(timeout),
+            None => return,
+        };
+        match output {
+            Some(Ok(items)) => {
+                self.filter = None;
+                let original = self.show_view(View::new(items));
+                // Replacing an earlier output keeps the original
+                if self.original.is_none() {
+                    self.original = Some(original);
+                }
+            }
+            Some(Err(error)) => {
+                self.filter = None;
+                self.filter_error = Some(error);
+            }
+            None => {}
+        }
+    }
+
+    /// Switch between the output of the filter and the original document
+    pub fn toggle_filter_output(&mut self) {
+        if let Some(original) = self.original.take() {
+            self.filter_output = Some(self.show_view(original));
+        } else if let Some(filter_output) = self.filter_output.take() {
+            self.original = Some(self.show_view(filter_output));
+        }
+    }
+
+    fn show_original(&mut self) {
+        self.filter_output = None;
+        if let Some(original) = self.original.take() {
+            self.show_view(original);
+        }
+    }
+
+    /// Show another document in the main list, returning the one that was shown. Search
+    /// results don't carry over.
+    fn show_view(&mut self, view: View) -> View {
+        let previous = View {
+            items: mem::replace(&mut self.items, view.items),
+            collapsed: mem::replace(&mut self.collapsed, view.collapsed),
+            selection: self.selection_index().unwrap_or(0),
+            top_index: self.top_index,
+        };
+        self.search = None;
+        self.search_results.clear();
+        self.results_panel.selected = 0;
+        self.search_state = NotSearching;
+        self.count_values();
+        self.recalculate_visible();
+        self.top_index = view.top_index;
+        self.select_line(view.selection);
+        previous
+    }
+
+    pub fn open_fuzzy_finder(&mut self) {
+        self.fuzzy_finder = Some(FuzzyFinder::new());
+    }
+
+    pub fn update_fuzzy_finder(&mut self, event: &Event) {
+        if let Some(fuzzy_finder) = &mut self.fuzzy_finder {
+            fuzzy_finder.update(event, &self.items);
+        }
+    }
+
+    /// Close the fuzzy finder and go to the chosen key
+    pub fn finish_fuzzy_finder(&mut self) {
+        let line_number = self
+            .fuzzy_finder
+            .take()
+            .and_then(|fuzzy_finder| fuzzy_finder.selected_line());
+        if let Some(line_number) = line_number {
+            // Show everything again if the pick isn't among the matches
+            if self.kept.as_ref().is_some_and(|kept| !kept[line_number]) {
+                self.toggle_matches_only();
+            }
+            self.expand_ancestors(line_number);
+            self.select_index(self.position_of(line_number));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::load::Progress;
+    use crate::parse_json::ItemBuilder;
+
+    /// An array of `{"id": null}` objects with about `num_items` items in all
+    fn app_state_with_items(num_items: usize) -> AppState {
+        let mut builder = ItemBuilder::new();
+        builder.open_array(None);
+        for _ in 0..num_items / 3 {
+            builder.open_object(None);
+            builder.value(Some("id".to_string()), JsonValueType::Null);
+            builder.close();
+        }
+        builder.close();
+        let mut app_state = AppState::new(Document {
+            items: builder.finish(),
+            filename: "test.json".to_string(),
+            lazy_document: None,
+        });
+        app_state.list_height = 50;
+        app_state.select_index(app_state.num_visible() / 2);
+        app_state
+    }
+
+    /// The fastest of a few runs of what each frame needs from the state
+    fn frame_time(app_state: &AppState) -> Duration {
+        (0..5)
+            .map(|_| {
+                let start = Instant::now();
+                for _ in 0..100 {
+                    assert_eq!(app_state.display_items().len(), 50);
+                    assert!(!app_state.status_text().is_empty());
+                    assert!(!app_state.breadbrumbs_text().is_empty());
+                }
+                start.elapsed() / 100
+            })
+            .min()
+            .unwrap()
+    }
+
+    /// Drawing a frame should take about as long for a large document as for a small one;
+    /// only lookups in the tree of visible lines grow, logarithmically
+    #[test]
+    fn frame_time_does_not_depend_on_document_size() {
+        let small = frame_time(&app_state_with_items(10_000));
+        let large = frame_time(&app_state_with_items(1_000_000));
+        assert!(large < small * 5, "{:?} vs {:?}", large, small);
+    }
+
+    #[test]
+    fn matches_only_keeps_the_root_after_a_leading_comment() {
+        let items = crate::lenient_json::parse_lenient_json_string("// c\n{\"a\": 1}", true);
+        let mut app_state = AppState::new(Document {
+            items,
+            filename: "test.json".to_string(),
+            lazy_document: None,
+        });
+        app_state.toggle_matches_only();
+        let visible: Vec<usize> = app_state.visible_lines_from(0).collect();
+        assert_eq!(visible, [1, 3]);
+    }
+
+    #[test]
+    fn restarting_a_search_forgets_the_chosen_result() {
+        let mut app_state = app_state_with_items(30);
+        app_state.search_input = app_state.search_input.clone().with_value("id".to_string());
+        app_state.start_search();
+        app_state.finish_searching();
+        app_state.next_search_result();
+        assert!(app_state.search_state == BrowsingSearch(Some(1)));
+
+        app_state.toggle_search_option(|options| options.values_only = true);
+        assert!(!matches!(app_state.search_state, BrowsingSearch(Some(_))));
+        // Nothing to go to, rather than a result that's gone
+        app_state.next_search_result();
+        app_state.previous_search_result();
+        assert!(app_state.search_results.is_empty());
+    }
+
+    /// A lazily loaded array of `len` objects like `{"id": 7, "tags": [7]}`
+    fn lazy_app_state(name: &str, len: usize) -> AppState {
+        let records: Vec<String> = (0..len)
+            .map(|i| format!("{{\"id\": {}, \"tags\": [{}]}}", i, i))
+            .collect();
+        let path = std::env::temp_dir().join(format!("jex-{}-{}.json", std::process::id(), name));
+        std::fs::write(&path, format!("[\n{}\n]\n", records.join(",\n"))).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        let lazy_document = LazyDocument::open(&file, &Progress::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut app_state = AppState::new(Document {
+            items: lazy_document.root_items(),
+            filename: name.to_string(),
+            lazy_document: Some(lazy_document),
+        });
+        app_state.list_height = 20;
+        app_state.select_top();
+        app_state
+    }
+
+    /// Check that line numbers, brackets, sibling chains and member indexes agree
+    fn check_links(items: &[JsonItem]) {
+        let mut children = vec![vec![]; items.len()];
+        for (line_number, item) in items.iter().enumerate() {
+            assert_eq!(item.line_number, line_number);
+            if let Some(bracket) = item.bracket {
+                assert_eq!(items[bracket].bracket, Some(line_number));
+            }
+            let is_closer = matches!(
+                item.value,
+                JsonValueType::ArrayEnd | JsonValueType::ObjectEnd
+            );
+            if let (Some(parent), false) = (item.parent, is_closer) {
+                assert!(parent < line_number && line_number < items[parent].bracket.unwrap());
+                children[parent].push(line_number);
+            }
+        }
+        for (line_number, item) in items.iter().enumerate() {
+            let chain: Vec<usize> =
+                std::iter::successors(item.first_child, |child| items[*child].next_sibling)
+                    .collect();
+            assert_eq!(
+                chain, children[line_number],
+                "children of line {}",
+                line_number
+            );
+        }
+        for chain in children {
+            let mut expected = 0;
+            for line in chain {
+                match &items[line].value {
+                    JsonValueType::Unloaded(unloaded) => {
+                        assert_eq!(unloaded.members.start, expected);
+                        expected = unloaded.members.end;
+                    }
+                    JsonValueType::Error(_) | JsonValueType::Comment(_) => {}
+                    _ => {
+                        assert_eq!(items[line].index_in_parent, expected);
+                        expected += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Position in the root array of the selected record
+    fn selected_record(app_state: &AppState) -> usize {
+        let item = &app_state.items[app_state.selection_index().unwrap()];
+        assert_eq!(item.parent, Some(0), "{} is not a record", item.value_str);
+        item.index_in_parent
+    }
+
+    /// Load what came into view, checking that the selection stays on the same row
+    fn load_items_in_view(app_state: &mut AppState) {
+        let row = app_state.list_state.selected().unwrap() - app_state.top_index;
+        app_state.load_items_in_view();
+        assert_eq!(
+            app_state.list_state.selected().unwrap() - app_state.top_index,
+            row
+        );
+    }
+
+    /// Expand a record and check that it holds its own id
+    fn check_record(app_state: &mut AppState, record: usize) {
+        let line_number = std::iter::successors(app_state.items[0].first_child, |child| {
+            app_state.items[*child].next_sibling
+        })
+        .find(|line| app_state.items[*line].index_in_parent == record)
+        .unwrap();
+        app_state.select_line(line_number);
+        app_state.toggle_collapsed();
+        check_links(&app_state.items);
+        let id = &app_state.items[line_number + 1];
+        assert_eq!(id.name.as_deref(), Some("id"));
+        assert_eq!(id.value_str, record.to_string());
+        assert_eq!(
+            app_state.items[line_number + 2].name.as_deref(),
+            Some("tags")
+        );
+    }
+
+    #[test]
+    fn scrolling_down_reads_every_page_in_order() {
+        let mut app_state = lazy_app_state("down", 2500);
+        check_links(&app_state.items);
+        for record in 0..2500 {
+            app_state.select_next(1);
+            load_items_in_view(&mut app_state);
+            assert_eq!(selected_record(&app_state), record);
+        }
+        check_links(&app_state.items);
+        assert!(!app_state
+            .items
+            .iter()
+            .any(|item| matches!(item.value, JsonValueType::Unloaded(_))));
+        for record in [0, 999, 1000, 1500, 1999, 2000, 2499] {
+            check_record(&mut app_state, record);
+        }
+    }
+
+    #[test]
+    fn scrolling_up_from_the_bottom_reads_every_page_in_order() {
+        let mut app_state = lazy_app_state("up", 3500);
+        app_state.select_bottom();
+        app_state.load_items_in_view();
+        for record in (0..3500).rev() {
+            app_state.select_previous(1);
+            load_items_in_view(&mut app_state);
+            assert_eq!(selected_record(&app_state), record);
+        }
+        check_links(&app_state.items);
+        for record in [0, 1000, 1999, 2000, 2999, 3499] {
+            check_record(&mut app_state, record);
+        }
+    }
+
+    #[test]
+    fn paging_up_from_the_bottom_skips_no_records() {
+        let mut app_state = lazy_app_state("page-up", 3500);
+        app_state.select_bottom();
+        app_state.select_previous(1);
+        let mut record = 3499;
+        while record >= 20 {
+            app_state.select_previous(20);
+            load_items_in_view(&mut app_state);
+            record -= 20;
+            assert_eq!(selected_record(&app_state), record);
+        }
+        check_links(&app_state.items);
+    }
+
+    #[test]
+    fn expanding_after_paging_keeps_the_links() {
+        let mut app_state = lazy_app_state("expand", 4500);
+        // Jump past the placeholder, which loads the page above the last one, and expand
+        // records on both sides of the pages that are still unloaded
+        app_state.select_index(1010);
+        app_state.load_items_in_view();
+        check_links(&app_state.items);
+        for record in [3600, 10, 4499, 999, 3000] {
+            check_record(&mut app_state, record);
+        }
+        app_state.select_bottom();
+        for _ in 0..4500 {
+            app_state.select_previous(1);
+            app_state.load_items_in_view();
+        }
+        check_links(&app_state.items);
+        for record in [1000, 2500, 2999] {
+            check_record(&mut app_state, record);
+        }
+    }
+
+    /// Where J and K went when they scanned the visible items for the same indent
+    fn scan_for_indent(app_state: &AppState, line_number: usize, forward: bool) -> Option<usize> {
+        let indent = app_state.object_indent(line_number);
+        let position = app_state.position_of(line_number);
+        let positions: Box<dyn Iterator<Item = usize>> = match forward {
+            true => Box::new(position + 1..app_state.num_visible()),
+            false => Box::new((0..position).rev()),
+        };
+        positions
+            .map(|position| app_state.line_at(position))
+            .find(|line| app_state.items[*line].indent == indent)
+    }
+
+    #[test]
+    fn next_and_previous_object_follow_the_indent() {
+        let text = r#"// header
+        {"a": [1, {"b": [2, 3], "c": {}}, [4, [5]]], "d": {"e": {"f": 6}, "g": [7]}, "h": 8}"#;
+        let items = crate::lenient_json::parse_lenient_json_string(text, true);
+        let mut app_state = AppState::new(Document {
+            items,
+            filename: "test.json".to_string(),
+            lazy_document: None,
+        });
+        app_state.list_height = 50;
+        // Everything expanded, then `{"b"…}` and `[5]`, `a` and `e`, and `d` collapsed
+        for collapsed in [vec![], vec![4, 14], vec![2, 20], vec![19]] {
+            app_state.uncollapse_all();
+            for line in collapsed {
+                app_state.set_collapsed(line, true);
+            }
+            let visible: Vec<usize> = app_state.visible_lines_from(0).collect();
+            for line in visible {
+                app_state.select_line(line);
+                let expected = scan_for_indent(&app_state, line, true).unwrap_or(line);
+                app_state.select_next_object();
+                assert_eq!(
+                    app_state.selection_index(),
+                    Some(expected),
+                    "J from {}",
+                    line
+                );
+
+                app_state.select_line(line);
+                let expected = scan_for_indent(&app_state, line, false).unwrap_or(line);
+                app_state.select_previous_object();
+                assert_eq!(
+                    app_state.selection_index(),
+                    Some(expected),
+                    "K from {}",
+                    line
+                );
+            }
+        }
+    }
 }
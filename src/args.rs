@@ -0,0 +1,115 @@
+use std::path::Path;
+use std::process::exit;
+
+use crate::decompress::COMPRESSED_EXTENSIONS;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    JsonLines,
+    Json5,
+    Yaml,
+    Toml,
+    MessagePack,
+    Cbor,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "jsonl" | "ndjson" => Some(Format::JsonLines),
+            "json5" | "jsonc" => Some(Format::Json5),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            "msgpack" | "mpk" | "mp" => Some(Format::MessagePack),
+            "cbor" => Some(Format::Cbor),
+            _ => None,
+        }
+    }
+}
+
+pub struct Args {
+    pub input_file: String,
+    pub format: Option<Format>,
+    pub lenient: bool,
+    pub lazy: bool,
+}
+
+impl Args {
+    pub fn parse() -> Args {
+        let mut input_file = None;
+        let mut format = None;
+        let mut lenient = false;
+        let mut lazy = false;
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => {
+                    format = Some(
+                        args.next()
+                            .and_then(|name| Format::from_name(&name))
+                            .unwrap_or_else(|| usage()),
+                    )
+                }
+                "--jsonl" => format = Some(Format::JsonLines),
+                "--lenient" => lenient = true,
+                "--lazy" => lazy = true,
+                "-h" | "--help" => usage(),
+                _ if arg.starts_with("--") => usage(),
+                _ if input_file.is_none() => input_file = Some(arg),
+                _ => usage(),
+            }
+        }
+        Args {
+            input_file: input_file.unwrap_or("-".to_string()),
+            format,
+            lenient,
+            lazy,
+        }
+    }
+
+    pub fn reads_stdin(&self) -> bool {
+        self.input_file == "-"
+    }
+
+    /// Format given on the command line, or guessed from the file extension
+    pub fn format_hint(&self) -> Option<Format> {
+        if self.format.is_some() {
+            return self.format;
+        }
+        let path = Path::new(&self.input_file);
+        // Look through the compression extension, e.g. `dump.json.zst`
+        let path = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if COMPRESSED_EXTENSIONS.contains(&ext) => {
+                Path::new(path.file_stem().unwrap_or_default())
+            }
+            _ => path,
+        };
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Format::from_name)
+    }
+}
+
+pub fn usage() -> ! {
+    println!("Usage: `jex [OPTIONS] [INPUT_FILE]`");
+    println!("Reads from stdin if INPUT_FILE is omitted or `-`.");
+    println!("Input compressed with gzip, zstd, bzip2 or xz is decompressed automatically.");
+    println!();
+    println!("Options:");
+    println!("  --format FORMAT  Input format: json, jsonl, json5, jsonc, yaml, toml,");
+    println!("                   msgpack or cbor");
+    println!("                   (default: guessed from the file extension and contents)");
+    println!("  --jsonl          Same as `--format jsonl`");
+    println!(
+        "  --lenient        Recover from syntax errors and show the parts that could be parsed"
+    );
+    println!("  --lazy           Memory-map the file and only parse the parts that are expanded");
+    println!("                   (default for JSON files of 1 GiB or more)");
+    println!();
+    println!(
+        "Exit status: 1 on usage errors, 2 if the input can't be read, 3 if it can't be parsed."
+    );
+    exit(1);
+}
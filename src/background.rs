@@ -0,0 +1,70 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Work running on a background thread, sending what it finds in batches. Dropping it
+/// tells the thread to stop.
+pub struct BackgroundWork<T> {
+    receiver: Receiver<T>,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// The thread's side of `BackgroundWork`
+pub struct Worker<T> {
+    sender: Sender<T>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<T: Send + 'static> BackgroundWork<T> {
+    pub fn start(work: impl FnOnce(Worker<T>) + Send + 'static) -> BackgroundWork<T> {
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let worker = Worker {
+            sender,
+            cancelled: cancelled.clone(),
+        };
+        thread::spawn(move || work(worker));
+        BackgroundWork {
+            receiver,
+            cancelled,
+        }
+    }
+
+    /// Pass the batches sent so far to `receive`, waiting up to `timeout` for the first
+    /// one. Returns false once the work is done and everything has been received.
+    pub fn receive(&self, timeout: Duration, mut receive: impl FnMut(T)) -> bool {
+        let mut timeout = timeout;
+        loop {
+            match self.receiver.recv_timeout(timeout) {
+                Ok(batch) => receive(batch),
+                Err(RecvTimeoutError::Timeout) => return true,
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+            timeout = Duration::ZERO;
+        }
+    }
+}
+
+impl<T> Drop for BackgroundWork<T> {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl<T> Worker<T> {
+    /// Returns false if nobody wants the batch anymore, to stop working
+    pub fn send(&self, batch: T) -> bool {
+        !self.is_cancelled() && self.sender.send(batch).is_ok()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// The flag that is set on cancelling, for code that checks it itself
+    pub fn cancelled(&self) -> &AtomicBool {
+        &self.cancelled
+    }
+}
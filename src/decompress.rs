@@ -0,0 +1,50 @@
+use std::io::{self, Cursor, Read};
+
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+/// File extensions of the compression formats handled here
+pub const COMPRESSED_EXTENSIONS: [&str; 4] = ["gz", "zst", "bz2", "xz"];
+
+/// Look at the first bytes of the input and, if they are the magic number of a known
+/// compression format, wrap the reader in a decoder. Other input is passed through.
+pub fn decompressing_reader<'a>(mut reader: impl Read + 'a) -> io::Result<Box<dyn Read + 'a>> {
+    let mut magic = [0u8; 6];
+    let mut num_read = 0;
+    while num_read < magic.len() {
+        match reader.read(&mut magic[num_read..])? {
+            0 => break,
+            n => num_read += n,
+        }
+    }
+    let magic = &magic[..num_read];
+    let reader = Cursor::new(magic.to_vec()).chain(reader);
+    if !is_compressed(magic) {
+        return Ok(Box::new(reader));
+    }
+
+    Ok(if magic.starts_with(GZIP_MAGIC) {
+        Box::new(MultiGzDecoder::new(reader))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        Box::new(zstd::Decoder::new(reader)?)
+    } else if magic.starts_with(BZIP2_MAGIC) {
+        Box::new(MultiBzDecoder::new(reader))
+    } else if magic.starts_with(XZ_MAGIC) {
+        Box::new(XzDecoder::new_multi_decoder(reader))
+    } else {
+        Box::new(reader)
+    })
+}
+
+/// Whether the bytes start with the magic number of a supported compression format
+pub fn is_compressed(bytes: &[u8]) -> bool {
+    [GZIP_MAGIC, ZSTD_MAGIC, BZIP2_MAGIC, XZ_MAGIC]
+        .iter()
+        .any(|magic| bytes.starts_with(magic))
+}
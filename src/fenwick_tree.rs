@@ -0,0 +1,137 @@
+/// Counts of set flags over a sequence, with O(log n) updates, prefix counts and lookup
+/// of the n-th set flag. Used to map between line numbers and positions on screen
+/// without materializing the list of visible items.
+pub struct FenwickTree {
+    // tree[i] holds the count for the range (i - lowest_bit(i), i], 1-based
+    tree: Vec<usize>,
+}
+
+impl FenwickTree {
+    pub fn from_flags(flags: impl ExactSizeIterator<Item = bool>) -> FenwickTree {
+        let mut tree = vec![0; flags.len() + 1];
+        for (index, flag) in flags.enumerate() {
+            tree[index + 1] += flag as usize;
+        }
+        // Push each partial count up to its parent, building the tree in linear time
+        for index in 1..tree.len() {
+            let parent = index + lowest_bit(index);
+            if parent < tree.len() {
+                tree[parent] += tree[index];
+            }
+        }
+        FenwickTree { tree }
+    }
+
+    pub fn set(&mut self, index: usize) {
+        let mut index = index + 1;
+        while index < self.tree.len() {
+            self.tree[index] += 1;
+            index += lowest_bit(index);
+        }
+    }
+
+    pub fn clear(&mut self, index: usize) {
+        let mut index = index + 1;
+        while index < self.tree.len() {
+            self.tree[index] -= 1;
+            index += lowest_bit(index);
+        }
+    }
+
+    /// Number of set flags before `index`
+    pub fn count_before(&self, index: usize) -> usize {
+        let mut index = index.min(self.tree.len() - 1);
+        let mut count = 0;
+        while index > 0 {
+            count += self.tree[index];
+            index -= lowest_bit(index);
+        }
+        count
+    }
+
+    pub fn count(&self) -> usize {
+        self.count_before(self.tree.len() - 1)
+    }
+
+    /// Index of the flag that has `n` set flags before it, if there are that many
+    pub fn find_nth(&self, n: usize) -> Option<usize> {
+        if n >= self.count() {
+            return None;
+        }
+        // Descend from the largest power of two, keeping the count below the position
+        let mut position = 0;
+        let mut remaining = n;
+        let mut step = (self.tree.len() - 1).next_power_of_two();
+        while step > 0 {
+            let next = position + step;
+            if next < self.tree.len() && self.tree[next] <= remaining {
+                position = next;
+                remaining -= self.tree[next];
+            }
+            step /= 2;
+        }
+        Some(position)
+    }
+}
+
+fn lowest_bit(index: usize) -> usize {
+    index & index.wrapping_neg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A xorshift generator, so the test sees the same updates every time
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    fn check(tree: &FenwickTree, flags: &[bool]) {
+        let set: Vec<usize> = (0..flags.len()).filter(|index| flags[*index]).collect();
+        assert_eq!(tree.count(), set.len());
+        for index in 0..=flags.len() {
+            let expected = flags[..index].iter().filter(|flag| **flag).count();
+            assert_eq!(tree.count_before(index), expected, "count before {}", index);
+        }
+        for n in 0..=set.len() {
+            assert_eq!(tree.find_nth(n), set.get(n).copied(), "{}th flag", n);
+        }
+    }
+
+    #[test]
+    fn agrees_with_a_list_of_flags_after_random_updates() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        for len in [1, 2, 3, 4, 7, 8, 9, 16, 31, 32, 33, 64, 100] {
+            let mut flags: Vec<bool> = (0..len).map(|_| random.below(2) == 0).collect();
+            let mut tree = FenwickTree::from_flags(flags.iter().copied());
+            check(&tree, &flags);
+            for _ in 0..200 {
+                let index = random.below(len);
+                match flags[index] {
+                    true => tree.clear(index),
+                    false => tree.set(index),
+                }
+                flags[index] = !flags[index];
+                check(&tree, &flags);
+            }
+        }
+    }
+
+    #[test]
+    fn all_clear_and_all_set() {
+        for len in [1, 2, 4, 5, 8] {
+            for flag in [false, true] {
+                let flags = vec![flag; len];
+                check(&FenwickTree::from_flags(flags.iter().copied()), &flags);
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossterm::event::Event;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use tui_input::backend::crossterm::EventHandler;
+use tui_input::Input;
+
+use crate::background::BackgroundWork;
+use crate::json_item::{JsonItem, JsonValueType};
+use crate::tree;
+
+/// Only the best matches are shown
+const MAX_MATCHES: usize = 200;
+
+/// The ranking found so far is sent after this many items
+const BATCH_SIZE: usize = 10_000;
+
+#[derive(Clone)]
+pub struct Match {
+    pub line_number: usize,
+    pub path: String,
+    score: i64,
+}
+
+impl Match {
+    /// Best first: higher scores, then shorter paths, then document order
+    fn rank(&self) -> (Reverse<i64>, usize, usize) {
+        (Reverse(self.score), self.path.len(), self.line_number)
+    }
+}
+
+/// A popup for finding keys by typing a few of their letters, like fzf. All values in the
+/// document, including array elements, are matched by their full path and ranked by how
+/// well they match.
+pub struct FuzzyFinder {
+    pub input: Input,
+    /// Best first
+    pub matches: Vec<Match>,
+    pub selected: usize,
+    search: Option<BackgroundFuzzySearch>,
+}
+
+impl FuzzyFinder {
+    pub fn new() -> FuzzyFinder {
+        FuzzyFinder {
+            input: Input::new("".to_string()),
+            matches: vec![],
+            selected: 0,
+            search: None,
+        }
+    }
+
+    pub fn update(&mut self, event: &Event, items: &Arc<Vec<JsonItem>>) {
+        if let Some(change) = self.input.handle_event(event) {
+            if change.value {
+                self.matches.clear();
+                self.selected = 0;
+                self.search = match self.input.value().is_empty() {
+                    true => None,
+                    false => Some(BackgroundFuzzySearch::start(
+                        items.clone(),
+                        self.input.value().to_string(),
+                    )),
+                };
+                self.receive_matches(Duration::from_millis(20));
+            }
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.search.is_some()
+    }
+
+    pub fn receive_matches(&mut self, timeout: Duration) {
+        if let Some(search) = &self.search {
+            if !search.receive(&mut self.matches, timeout) {
+                self.search = None;
+            }
+            self.selected = self.selected.min(self.matches.len().saturating_sub(1));
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1));
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn selected_line(&self) -> Option<usize> {
+        self.matches.get(self.selected).map(|m| m.line_number)
+    }
+
+    /// Positions of the characters of `path` that match the input, for highlighting
+    pub fn matched_chars(&self, path: &str) -> Vec<usize> {
+        matcher()
+            .fuzzy_indices(path, self.input.value())
+            .map(|(_, indices)| indices)
+            .unwrap_or_default()
+    }
+}
+
+/// A short summary of the value of an item
+pub fn value_preview(item: &JsonItem) -> String {
+    match &item.value {
+        JsonValueType::Array => format!("[{} items]", item.len),
+        JsonValueType::Object => format!("{{{} items}}", item.len),
+        JsonValueType::String(s) => format!("\"{}\"", s),
+        JsonValueType::Null => "null".to_string(),
+        _ => item.value_str.clone(),
+    }
+}
+
+/// The root has an empty path, even after a leading comment, and closing brackets and
+/// annotations only share the path of their container
+fn has_path(item: &JsonItem) -> bool {
+    item.parent.is_some()
+        && !matches!(
+            item.value,
+            JsonValueType::ArrayEnd
+                | JsonValueType::ObjectEnd
+                | JsonValueType::Error(_)
+                | JsonValueType::Comment(_)
+                | JsonValueType::Unloaded(_)
+        )
+}
+
+fn matcher() -> SkimMatcherV2 {
+    // `usrPrefTz` should find `userPreferences ▶ timezone`
+    SkimMatcherV2::default().ignore_case()
+}
+
+/// Ranks the keys on a background thread. Each batch sends the best matches so far.
+struct BackgroundFuzzySearch(BackgroundWork<Vec<Match>>);
+
+impl BackgroundFuzzySearch {
+    fn start(items: Arc<Vec<JsonItem>>, pattern: String) -> BackgroundFuzzySearch {
+        BackgroundFuzzySearch(BackgroundWork::start(move |worker| {
+            let matcher = matcher();
+            // The worst of the best matches is on top
+            let mut best = BinaryHeap::new();
+            for batch in items.chunks(BATCH_SIZE) {
+                if worker.is_cancelled() {
+                    return;
+                }
+                for item in batch.iter().filter(|item| has_path(item)) {
+                    let path = tree::breadcrumbs(&items, item.line_number);
+                    if let Some(score) = matcher.fuzzy_match(&path, &pattern) {
+                        let candidate = Match {
+                            line_number: item.line_number,
+                            path,
+                            score,
+                        };
+                        best.push(RankedMatch(candidate));
+                        if best.len() > MAX_MATCHES {
+                            best.pop();
+                        }
+                    }
+                }
+                let mut matches: Vec<Match> = best.iter().map(|m| m.0.clone()).collect();
+                matches.sort_by_key(Match::rank);
+                if !worker.send(matches) {
+                    return;
+                }
+            }
+        }))
+    }
+
+    /// Replace `matches` with the latest ranking. Returns false once the search is complete.
+    fn receive(&self, matches: &mut Vec<Match>, timeout: Duration) -> bool {
+        self.0.receive(timeout, |ranking| *matches = ranking)
+    }
+}
+
+/// Orders matches worst first, so that the heap drops the worst one
+struct RankedMatch(Match);
+
+impl Ord for RankedMatch {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.rank().cmp(&other.0.rank())
+    }
+}
+
+impl PartialOrd for RankedMatch {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RankedMatch {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.rank() == other.0.rank()
+    }
+}
+
+impl Eq for RankedMatch {}
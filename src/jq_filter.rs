@@ -0,0 +1,237 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use jaq_core::load::{self, Arena, File, Loader};
+use jaq_core::{Compiler, Ctx, RcIter};
+use jaq_json::Val;
+use serde_json::{Map, Value};
+
+use crate::background::BackgroundWork;
+use crate::json_item::{JsonItem, JsonValueType};
+use crate::lazy_json::LazyDocument;
+use crate::parse_json::{self, ItemBuilder};
+use crate::tree;
+
+/// Filters that produce more results than this are stopped, e.g. `repeat(1)`
+const MAX_OUTPUTS: usize = 1_000_000;
+
+type FilterOutput = Result<Vec<JsonItem>, String>;
+
+/// A jq filter running on a background thread. Its output becomes a new document: the
+/// single result itself, or an array of all results. Dropping the filter stops it at
+/// the next result.
+pub struct BackgroundFilter(BackgroundWork<FilterOutput>);
+
+/// The document that filters run on. The first filter converts it to a JSON value on its
+/// thread, and the value is kept for the ones after it.
+#[derive(Clone)]
+pub struct DocumentValue {
+    source: Arc<Mutex<Source>>,
+    lazy_document: Option<Arc<LazyDocument>>,
+}
+
+enum Source {
+    Items(Arc<Vec<JsonItem>>),
+    Value(Arc<Value>),
+}
+
+impl DocumentValue {
+    pub fn new(items: Arc<Vec<JsonItem>>, lazy_document: Option<Arc<LazyDocument>>) -> Self {
+        DocumentValue {
+            source: Arc::new(Mutex::new(Source::Items(items))),
+            lazy_document,
+        }
+    }
+
+    /// Filters started while the value is converted wait for it. If the conversion is
+    /// cancelled, the next filter starts over.
+    fn get(&self, cancelled: &AtomicBool) -> Result<Arc<Value>, String> {
+        let mut source = self.source.lock().unwrap();
+        let items = match &*source {
+            Source::Value(value) => return Ok(value.clone()),
+            Source::Items(items) => items,
+        };
+        let value = match tree::root(items) {
+            Some(root) => item_value(items, root, self.lazy_document.as_deref(), cancelled)?,
+            None => Value::Null,
+        };
+        let value = Arc::new(value);
+        // The items aren't needed anymore, and holding on to them would make loading
+        // more of a lazy document copy them
+        *source = Source::Value(value.clone());
+        Ok(value)
+    }
+}
+
+impl BackgroundFilter {
+    pub fn start(input: DocumentValue, code: String) -> BackgroundFilter {
+        BackgroundFilter(BackgroundWork::start(move |worker| {
+            if let Some(output) = run(&input, &code, worker.cancelled()) {
+                worker.send(output);
+            }
+        }))
+    }
+
+    /// The items of the output document or why there are none, waiting up to `timeout`
+    /// for them. Returns `None` while the filter is still running.
+    pub fn receive(&self, timeout: Duration) -> Option<FilterOutput> {
+        let mut output = None;
+        let running = self.0.receive(timeout, |received| output = Some(received));
+        match (output, running) {
+            (Some(output), _) => Some(output),
+            (None, true) => None,
+            (None, false) => Some(Err("Filter failed".to_string())),
+        }
+    }
+}
+
+/// Returns `None` if the filter was cancelled
+fn run(input: &DocumentValue, code: &str, cancelled: &AtomicBool) -> Option<FilterOutput> {
+    let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+    let arena = Arena::default();
+    let modules = match loader.load(&arena, File { code, path: () }) {
+        Ok(modules) => modules,
+        Err(errors) => return Some(Err(load_error_message(code, errors))),
+    };
+    let filter = match Compiler::default()
+        .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+        .compile(modules)
+    {
+        Ok(filter) => filter,
+        Err(errors) => {
+            let message = errors
+                .into_iter()
+                .flat_map(|(_, errors)| errors)
+                .map(|(name, undefined)| format!("undefined {} `{}`", undefined.as_str(), name))
+                .next()
+                .unwrap_or_default();
+            return Some(Err(format!("Invalid filter: {}", message)));
+        }
+    };
+
+    let input = match input.get(cancelled) {
+        Ok(input) => input,
+        Err(_) if cancelled.load(Ordering::Relaxed) => return None,
+        Err(error) => return Some(Err(error)),
+    };
+    let inputs = RcIter::new(core::iter::empty());
+    let mut outputs = vec![];
+    for output in filter.run((Ctx::new([], &inputs), Val::from((*input).clone()))) {
+        if cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+        outputs.push(output.map(Value::from).map_err(|error| error.to_string()));
+        if outputs.len() == MAX_OUTPUTS {
+            outputs.push(Err(format!("stopped after {} results", MAX_OUTPUTS)));
+            break;
+        }
+    }
+
+    let mut builder = ItemBuilder::new();
+    match outputs.as_slice() {
+        // A lone error is most likely an unfinished filter, so keep showing the last output
+        [Err(error)] => return Some(Err(error.clone())),
+        [Ok(value)] => parse_json::parse_json(value, &mut builder, None),
+        _ => {
+            builder.open_array(None);
+            for (index, output) in outputs.iter().enumerate() {
+                builder.label_next(format!("[result {}]", index + 1));
+                match output {
+                    Ok(value) => parse_json::parse_json(value, &mut builder, None),
+                    Err(error) => builder.error(error.clone()),
+                }
+            }
+        }
+    }
+    Some(Ok(builder.finish()))
+}
+
+fn load_error_message(code: &str, errors: load::Errors<&str, ()>) -> String {
+    // Errors point into the code; report where
+    let column = |rest: &str| {
+        let offset = (rest.as_ptr() as usize)
+            .checked_sub(code.as_ptr() as usize)
+            .filter(|offset| *offset <= code.len())
+            .unwrap_or(code.len());
+        code[..offset].chars().count() + 1
+    };
+    let message = match errors.into_iter().next().map(|(_, error)| error) {
+        Some(load::Error::Io(errors)) => errors.into_iter().next().map(|(_, error)| error),
+        Some(load::Error::Lex(errors)) => errors.first().map(|(expected, rest)| {
+            format!("expected {} at column {}", expected.as_str(), column(rest))
+        }),
+        Some(load::Error::Parse(errors)) => errors.first().map(|(expected, found)| {
+            format!("expected {} at column {}", expected.as_str(), column(found))
+        }),
+        None => None,
+    };
+    format!("Invalid filter: {}", message.unwrap_or_default())
+}
+
+/// An item as a JSON value, leaving out comments and errors. Containers that haven't
+/// been loaded in full are parsed from `lazy_document`. Fails if that is malformed, or
+/// if the filter is cancelled.
+fn item_value(
+    items: &[JsonItem],
+    index: usize,
+    lazy_document: Option<&LazyDocument>,
+    cancelled: &AtomicBool,
+) -> Result<Value, String> {
+    if cancelled.load(Ordering::Relaxed) {
+        return Err("cancelled".to_string());
+    }
+    let item = &items[index];
+    let children = || std::iter::successors(item.first_child, |child| items[*child].next_sibling);
+    if let Some(lazy_document) = lazy_document {
+        if let Some(span) = &item.unloaded_span {
+            return lazy_document.value(span.start);
+        }
+        // Some pages of members may still be missing
+        for child in children() {
+            if let JsonValueType::Unloaded(unloaded) = &items[child].value {
+                return lazy_document.value(unloaded.container);
+            }
+        }
+    }
+    let children = children().filter(|child| {
+        !matches!(
+            items[*child].value,
+            JsonValueType::Error(_) | JsonValueType::Comment(_) | JsonValueType::Unloaded(_)
+        )
+    });
+    Ok(match &item.value {
+        JsonValueType::Object => {
+            let mut map = Map::new();
+            for child in children {
+                let name = items[child].name.clone().unwrap_or_default();
+                map.insert(name, item_value(items, child, lazy_document, cancelled)?);
+            }
+            Value::Object(map)
+        }
+        JsonValueType::Array => Value::Array(
+            children
+                .map(|child| item_value(items, child, lazy_document, cancelled))
+                .collect::<Result<_, _>>()?,
+        ),
+        JsonValueType::Number(n) => Value::Number(n.clone()),
+        JsonValueType::String(s) | JsonValueType::DateTime(s) => Value::String(s.clone()),
+        JsonValueType::Bool(b) => Value::Bool(*b),
+        JsonValueType::Bytes(bytes) => Value::Array(bytes.iter().map(|b| (*b).into()).collect()),
+        _ => Value::Null,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lenient_json::parse_lenient_json_string;
+
+    #[test]
+    fn input_is_the_root_value_after_a_leading_comment() {
+        let items = parse_lenient_json_string("// header\n{\"a\": [1, 2]} // end", true);
+        let input = DocumentValue::new(Arc::new(items), None);
+        let value = input.get(&AtomicBool::new(false)).unwrap();
+        assert_eq!(*value, serde_json::json!({"a": [1, 2]}));
+    }
+}
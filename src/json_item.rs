@@ -1,7 +1,10 @@
+use std::ops::Range;
+
 use ratatui::prelude::*;
 use serde_json::Number;
 
 use crate::theme::THEME;
+use thousands::Separable;
 
 #[derive(Clone, PartialEq)]
 pub enum JsonValueType {
@@ -13,6 +16,32 @@ pub enum JsonValueType {
     Object,
     ObjectEnd,
     Null,
+    Error(String),
+    Comment(String),
+    DateTime(String),
+    Bytes(Vec<u8>),
+    /// Members of a lazily loaded container that haven't been read yet. Like errors and
+    /// comments, this is not counted as a child.
+    Unloaded(UnloadedMembers),
+}
+
+/// A run of members of a lazily loaded container, read when they come into view
+#[derive(Clone, PartialEq)]
+pub struct UnloadedMembers {
+    /// Byte offset of the container in the file
+    pub container: usize,
+    /// Positions of the members in the container
+    pub members: Range<usize>,
+}
+
+/// How an item is shown at the moment, as opposed to what it contains
+pub struct DisplayState {
+    pub collapsed: bool,
+    pub selected: bool,
+    /// Indent level of the guide to highlight, that of the container holding the selection
+    pub selection_level: Option<usize>,
+    pub name_is_search_result: bool,
+    pub value_is_search_result: bool,
 }
 
 #[derive(Clone)]
@@ -22,27 +51,31 @@ pub struct JsonItem {
     pub value: JsonValueType,
     pub value_str: String,
     pub line_number: usize,
-    pub collapsed: bool,
-    pub visible: bool,
-    pub breadcrumbs: String,
-    pub selection_level: Option<usize>,
-    pub name_is_search_result: bool,
-    pub value_is_search_result: bool,
     pub len: usize,
+    pub tag: Option<String>,
+    pub unloaded_span: Option<Range<usize>>,
+    /// Shown in the breadcrumbs instead of the key or index, e.g. `[line 3]` in JSON Lines
+    pub label: Option<String>,
+    /// Position among the members of the parent container; annotations don't count
+    pub index_in_parent: usize,
+    // Links to other items, by line number. Closing brackets have the parent of their
+    // container and link back to it; they are not counted as children.
+    pub parent: Option<usize>,
+    pub first_child: Option<usize>,
+    pub next_sibling: Option<usize>,
+    pub bracket: Option<usize>,
 }
 
 impl JsonItem {
-    pub fn new(
-        name: Option<String>,
-        indent: usize,
-        value: JsonValueType,
-        breadcrumbs: String,
-        len: usize,
-    ) -> JsonItem {
+    pub fn new(name: Option<String>, indent: usize, value: JsonValueType, len: usize) -> JsonItem {
         let value_str = match &value {
             JsonValueType::Number(n) => n.to_string(),
             JsonValueType::String(s) => s.to_string(),
             JsonValueType::Bool(b) => b.to_string(),
+            JsonValueType::Error(e) => e.to_string(),
+            JsonValueType::Comment(c) => c.to_string(),
+            JsonValueType::DateTime(d) => d.to_string(),
+            JsonValueType::Bytes(bytes) => hex_preview(bytes),
             _ => "".to_string(),
         };
         JsonItem {
@@ -51,20 +84,37 @@ impl JsonItem {
             value,
             value_str,
             line_number: 0,
-            collapsed: false,
-            visible: true,
-            breadcrumbs,
-            selection_level: None,
-            name_is_search_result: false,
-            value_is_search_result: false,
             len,
+            tag: None,
+            unloaded_span: None,
+            label: None,
+            index_in_parent: 0,
+            parent: None,
+            first_child: None,
+            next_sibling: None,
+            bracket: None,
+        }
+    }
+
+    /// Move links that point at or past `index` by `offset`, for when `offset` items are
+    /// inserted at `index`
+    pub fn shift_links(&mut self, index: usize, offset: usize) {
+        for link in [
+            &mut self.parent,
+            &mut self.first_child,
+            &mut self.next_sibling,
+            &mut self.bracket,
+        ] {
+            if let Some(link) = link.as_mut().filter(|link| **link >= index) {
+                *link += offset;
+            }
         }
     }
 
-    fn indent_spans(&self) -> Vec<Span> {
+    fn indent_spans(&self, state: &DisplayState) -> Vec<Span<'_>> {
         let mut output = vec![];
         for i in 0..self.indent {
-            if Some(i) == self.selection_level {
+            if Some(i) == state.selection_level {
                 output.push(Span::styled(
                     "  │ ",
                     Style::default().fg(THEME.selection_level_indicator_color),
@@ -79,17 +129,17 @@ impl JsonItem {
         output
     }
 
-    pub fn display_text(&self, selection_index: Option<usize>) -> Line {
+    pub fn display_text(&self, state: &DisplayState) -> Line<'_> {
         let line_number = Span::styled(
             format!("{:8} ", self.line_number),
             Style::default().fg(Color::DarkGray),
         );
-        let selection_span = if selection_index == Some(self.line_number) {
+        let selection_span = if state.selected {
             Span::styled("▶ ", Style::default().fg(THEME.selection_indicator_color))
         } else {
             Span::raw("  ")
         };
-        let indents = self.indent_spans();
+        let indents = self.indent_spans(state);
 
         let name_str = match &self.name {
             Some(name) => format!("{}: ", name),
@@ -99,12 +149,12 @@ impl JsonItem {
             name_str.clone(),
             Style::default()
                 .fg(THEME.name_color)
-                .bg(match self.name_is_search_result {
+                .bg(match state.name_is_search_result {
                     true => THEME.search_indicator_color,
                     false => Color::default(),
                 }),
         );
-        let value_bg = match self.value_is_search_result {
+        let value_bg = match state.value_is_search_result {
             true => THEME.search_indicator_color,
             false => Color::default(),
         };
@@ -123,6 +173,25 @@ impl JsonItem {
                 );
                 vec![name_span, value_span]
             }
+            JsonValueType::DateTime(d) => {
+                let value_span = Span::styled(
+                    d.to_string(),
+                    Style::default().fg(THEME.date_color).bg(value_bg),
+                );
+                vec![name_span, value_span]
+            }
+            JsonValueType::Bytes(bytes) => {
+                let value_span = Span::styled(
+                    format!(
+                        "<{} byte{}: {}>",
+                        bytes.len(),
+                        if bytes.len() == 1 { "" } else { "s" },
+                        hex_preview(bytes)
+                    ),
+                    Style::default().fg(THEME.bytes_color).bg(value_bg),
+                );
+                vec![name_span, value_span]
+            }
             JsonValueType::Bool(b) => {
                 let value_span = Span::styled(
                     format!("{}", b),
@@ -131,10 +200,10 @@ impl JsonItem {
                 vec![name_span, value_span]
             }
             JsonValueType::Array => {
-                if self.collapsed {
+                if state.collapsed {
                     vec![
                         name_span,
-                        Span::from("["),
+                        Span::styled("[", Style::default().bg(value_bg)),
                         Span::styled(
                             format!("{} items", self.len),
                             Style::default().fg(Color::DarkGray),
@@ -142,7 +211,7 @@ impl JsonItem {
                         Span::from("]"),
                     ]
                 } else {
-                    let brackets_span = Span::from("[");
+                    let brackets_span = Span::styled("[", Style::default().bg(value_bg));
                     vec![name_span, brackets_span]
                 }
             }
@@ -151,10 +220,10 @@ impl JsonItem {
                 vec![brackets_span]
             }
             JsonValueType::Object => {
-                if self.collapsed {
+                if state.collapsed {
                     vec![
                         name_span,
-                        Span::from("{"),
+                        Span::styled("{", Style::default().bg(value_bg)),
                         Span::styled(
                             format!("{} items", self.len),
                             Style::default().fg(Color::DarkGray),
@@ -162,7 +231,7 @@ impl JsonItem {
                         Span::from("}"),
                     ]
                 } else {
-                    let brackets_span = Span::from("{");
+                    let brackets_span = Span::styled("{", Style::default().bg(value_bg));
                     vec![name_span, brackets_span]
                 }
             }
@@ -171,10 +240,65 @@ impl JsonItem {
                 vec![brackets_span]
             }
             JsonValueType::Null => {
-                let value_span = Span::styled("null", Style::default().fg(THEME.null_color));
+                let value_span =
+                    Span::styled("null", Style::default().fg(THEME.null_color).bg(value_bg));
                 vec![name_span, value_span]
             }
+            JsonValueType::Error(e) => {
+                let value_span = Span::styled(
+                    format!("✗ {}", e),
+                    Style::default().fg(THEME.error_color).bg(value_bg),
+                );
+                vec![name_span, value_span]
+            }
+            JsonValueType::Comment(c) => {
+                let value_span = Span::styled(
+                    format!("// {}", c),
+                    Style::default().fg(THEME.comment_color).bg(value_bg),
+                );
+                vec![value_span]
+            }
+            JsonValueType::Unloaded(unloaded) => {
+                let value_span = Span::styled(
+                    format!("… {} more", unloaded.members.len().separate_with_commas()),
+                    Style::default().fg(THEME.comment_color),
+                );
+                vec![value_span]
+            }
         };
-        Line::from([vec![line_number], indents, vec![selection_span], name_value].concat())
+        let tag = match &self.tag {
+            Some(tag) => vec![Span::styled(
+                format!("({}) ", tag),
+                Style::default().fg(THEME.tag_color),
+            )],
+            None => vec![],
+        };
+        let (name, value) = name_value.split_at(name_value.len().min(1));
+        Line::from(
+            [
+                vec![line_number],
+                indents,
+                vec![selection_span],
+                name.to_vec(),
+                tag,
+                value.to_vec(),
+            ]
+            .concat(),
+        )
+    }
+}
+
+const HEX_PREVIEW_BYTES: usize = 16;
+
+/// Hex dump of the first few bytes
+fn hex_preview(bytes: &[u8]) -> String {
+    let mut preview: Vec<String> = bytes
+        .iter()
+        .take(HEX_PREVIEW_BYTES)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if bytes.len() > HEX_PREVIEW_BYTES {
+        preview.push("…".to_string());
     }
+    preview.join(" ")
 }
@@ -0,0 +1,786 @@
+use std::cmp::Ordering;
+use std::sync::atomic::{self, AtomicBool};
+
+use crate::json_item::{JsonItem, JsonValueType};
+use crate::tree;
+
+/// A JSONPath expression like `$.items[*].metadata.labels[?(@.tier=='db')]`, evaluated on
+/// the items of a document. Supports member names, wildcards, indexes, slices, unions,
+/// recursive descent (`..`) and filters with comparisons, `&&`, `||` and `!`.
+///
+/// Unlike RFC 9535, a filter on an object also tests the object itself, besides its
+/// members. That way the example above finds the `labels` objects with that tier.
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+enum Segment {
+    Child(Vec<Selector>),
+    Descendant(Vec<Selector>),
+}
+
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Expression),
+}
+
+enum Expression {
+    Or(Box<Expression>, Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Exists(Query),
+    Compare(Operand, Comparison, Operand),
+}
+
+/// A path inside a filter, starting at the current node (`@`) or at the root (`$`)
+struct Query {
+    relative: bool,
+    path: JsonPath,
+}
+
+enum Operand {
+    Query(Query),
+    Literal(Literal),
+}
+
+/// A scalar to compare with, also used by the typed predicates of plain searches
+#[derive(Clone, PartialEq)]
+pub enum Literal {
+    Number(f64),
+    String(String),
+    Bool(bool),
+    Null,
+}
+
+#[derive(Clone, Copy)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl JsonPath {
+    pub fn parse(text: &str) -> Result<JsonPath, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            position: 0,
+        };
+        parser.expect('$')?;
+        let path = parser.path()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(path),
+            Some(c) => Err(parser.error(&format!("unexpected `{}`", c))),
+        }
+    }
+
+    /// Line numbers of the matching items, in document order. Stops early, with some of
+    /// the items, once `cancelled` is set.
+    pub fn evaluate(&self, items: &[JsonItem], cancelled: &AtomicBool) -> Vec<usize> {
+        let mut nodes = match tree::root(items) {
+            Some(root) => {
+                let context = Context {
+                    items,
+                    root,
+                    cancelled,
+                };
+                self.select(&context, root)
+            }
+            None => vec![],
+        };
+        nodes.sort_unstable();
+        nodes.dedup();
+        nodes
+    }
+
+    fn select(&self, context: &Context, start: usize) -> Vec<usize> {
+        let mut nodes = vec![start];
+        for segment in &self.segments {
+            let mut selected = vec![];
+            for node in nodes {
+                match segment {
+                    Segment::Child(selectors) => {
+                        for selector in selectors {
+                            selector.select(context, node, &mut selected);
+                        }
+                    }
+                    Segment::Descendant(selectors) => {
+                        for descendant in descendants_or_self(context.items, node) {
+                            if context.is_cancelled() {
+                                break;
+                            }
+                            for selector in selectors {
+                                selector.select(context, descendant, &mut selected);
+                            }
+                        }
+                    }
+                }
+                if context.is_cancelled() {
+                    return vec![];
+                }
+            }
+            nodes = selected;
+        }
+        nodes
+    }
+}
+
+/// What selecting needs besides the current node
+struct Context<'a> {
+    items: &'a [JsonItem],
+    root: usize,
+    /// Set when the search is no longer needed, e.g. because the search text changed
+    cancelled: &'a AtomicBool,
+}
+
+impl Context<'_> {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(atomic::Ordering::Relaxed)
+    }
+}
+
+impl Selector {
+    fn select(&self, context: &Context, node: usize, selected: &mut Vec<usize>) {
+        let items = context.items;
+        let item = &items[node];
+        match self {
+            Selector::Name(name) => {
+                if item.value == JsonValueType::Object {
+                    selected.extend(
+                        children(items, node)
+                            .filter(|child| items[*child].name.as_ref() == Some(name)),
+                    );
+                }
+            }
+            Selector::Wildcard => selected.extend(children(items, node)),
+            Selector::Index(index) => {
+                if item.value == JsonValueType::Array {
+                    if let Some(index) = normalize(*index, item.len) {
+                        selected.extend(children(items, node).nth(index));
+                    }
+                }
+            }
+            Selector::Slice(start, end, step) => {
+                if item.value == JsonValueType::Array {
+                    let elements: Vec<usize> = children(items, node).collect();
+                    selected.extend(slice(&elements, *start, *end, step.unwrap_or(1)));
+                }
+            }
+            Selector::Filter(expression) => {
+                // Checked per member, since one array can hold most of the document
+                selected.extend(
+                    children(items, node)
+                        .take_while(|_| !context.is_cancelled())
+                        .filter(|child| expression.test(context, *child)),
+                );
+                // An extension, see `JsonPath`
+                if item.value == JsonValueType::Object && expression.test(context, node) {
+                    selected.push(node);
+                }
+            }
+        }
+    }
+}
+
+impl Expression {
+    fn test(&self, context: &Context, node: usize) -> bool {
+        match self {
+            Expression::Or(left, right) => left.test(context, node) || right.test(context, node),
+            Expression::And(left, right) => left.test(context, node) && right.test(context, node),
+            Expression::Not(expression) => !expression.test(context, node),
+            Expression::Exists(query) => !query.select(context, node).is_empty(),
+            Expression::Compare(left, comparison, right) => {
+                let left = left.value(context, node);
+                let right = right.value(context, node);
+                compare(left.as_ref(), *comparison, right.as_ref())
+            }
+        }
+    }
+}
+
+impl Query {
+    fn select(&self, context: &Context, node: usize) -> Vec<usize> {
+        let start = if self.relative { node } else { context.root };
+        self.path.select(context, start)
+    }
+}
+
+impl Operand {
+    /// The value to compare, or `None` if a query selects nothing or a container
+    fn value(&self, context: &Context, node: usize) -> Option<Literal> {
+        match self {
+            Operand::Literal(literal) => Some(literal.clone()),
+            Operand::Query(query) => {
+                let node = *query.select(context, node).first()?;
+                Literal::of(&context.items[node].value)
+            }
+        }
+    }
+}
+
+impl Literal {
+    /// The scalar value of an item, or `None` for containers and annotations
+    pub fn of(value: &JsonValueType) -> Option<Literal> {
+        match value {
+            JsonValueType::Number(n) => n.as_f64().map(Literal::Number),
+            JsonValueType::String(s) | JsonValueType::DateTime(s) => {
+                Some(Literal::String(s.clone()))
+            }
+            JsonValueType::Bool(b) => Some(Literal::Bool(*b)),
+            JsonValueType::Null => Some(Literal::Null),
+            _ => None,
+        }
+    }
+}
+
+/// Numbers and strings are ordered; other values can only be equal. A missing value
+/// only equals another missing value.
+pub fn compare(left: Option<&Literal>, comparison: Comparison, right: Option<&Literal>) -> bool {
+    let ordering = match (left, right) {
+        (Some(Literal::Number(a)), Some(Literal::Number(b))) => a.partial_cmp(b),
+        (Some(Literal::String(a)), Some(Literal::String(b))) => Some(a.cmp(b)),
+        (a, b) if a == b => Some(Ordering::Equal),
+        _ => None,
+    };
+    match comparison {
+        Comparison::Equal => ordering == Some(Ordering::Equal),
+        Comparison::NotEqual => ordering != Some(Ordering::Equal),
+        Comparison::Less => ordering == Some(Ordering::Less),
+        Comparison::LessOrEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        Comparison::Greater => ordering == Some(Ordering::Greater),
+        Comparison::GreaterOrEqual => {
+            matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+        }
+    }
+}
+
+/// Members of a container, without comments and error markers
+fn children(items: &[JsonItem], node: usize) -> impl Iterator<Item = usize> + '_ {
+    std::iter::successors(items[node].first_child, |child| items[*child].next_sibling).filter(
+        |child| {
+            !matches!(
+                items[*child].value,
+                JsonValueType::Error(_) | JsonValueType::Comment(_) | JsonValueType::Unloaded(_)
+            )
+        },
+    )
+}
+
+/// The node and all containers inside it, in document order
+fn descendants_or_self(items: &[JsonItem], node: usize) -> impl Iterator<Item = usize> + '_ {
+    let end = items[node].bracket.unwrap_or(node);
+    (node..end).filter(|line| {
+        matches!(
+            items[*line].value,
+            JsonValueType::Array | JsonValueType::Object
+        )
+    })
+}
+
+/// Negative indexes count from the end
+fn normalize(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+fn slice(elements: &[usize], start: Option<i64>, end: Option<i64>, step: i64) -> Vec<usize> {
+    let len = elements.len() as i64;
+    let bound = |index: i64| {
+        if index < 0 {
+            (len + index).max(-1)
+        } else {
+            index.min(len)
+        }
+    };
+    let mut selected = vec![];
+    if step > 0 {
+        let (start, end) = (start.map_or(0, bound).max(0), end.map_or(len, bound));
+        let mut index = start;
+        while index < end {
+            selected.push(elements[index as usize]);
+            index += step;
+        }
+    } else if step < 0 {
+        let (start, end) = (
+            start.map_or(len - 1, bound).min(len - 1),
+            end.map_or(-1, bound),
+        );
+        let mut index = start;
+        while index > end {
+            selected.push(elements[index as usize]);
+            index += step;
+        }
+    }
+    selected
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        format!("{} at column {}", message, self.position + 1)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_str(&self, s: &str) -> bool {
+        let end = self.position + s.chars().count();
+        end <= self.chars.len() && self.chars[self.position..end].iter().copied().eq(s.chars())
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        self.skip_whitespace();
+        let found = self.peek_str(s);
+        if found {
+            self.position += s.chars().count();
+        }
+        found
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.eat(&c.to_string()) {
+            true => Ok(()),
+            false => Err(self.error(&format!("expected `{}`", c))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn path(&mut self) -> Result<JsonPath, String> {
+        let mut segments = vec![];
+        loop {
+            if self.peek_str("..") {
+                self.position += 2;
+                let selectors = match self.peek() {
+                    Some('[') => self.bracketed()?,
+                    _ => vec![self.dot_selector()?],
+                };
+                segments.push(Segment::Descendant(selectors));
+            } else if self.peek_str(".") {
+                self.position += 1;
+                segments.push(Segment::Child(vec![self.dot_selector()?]));
+            } else if self.peek_str("[") {
+                segments.push(Segment::Child(self.bracketed()?));
+            } else {
+                return Ok(JsonPath { segments });
+            }
+        }
+    }
+
+    /// The part after `.`: a member name or `*`
+    fn dot_selector(&mut self) -> Result<Selector, String> {
+        if self.peek() == Some('*') {
+            self.position += 1;
+            return Ok(Selector::Wildcard);
+        }
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-' || !c.is_ascii())
+        {
+            self.position += 1;
+        }
+        match self.position > start {
+            true => Ok(Selector::Name(
+                self.chars[start..self.position].iter().collect(),
+            )),
+            false => Err(self.error("expected a member name")),
+        }
+    }
+
+    fn bracketed(&mut self) -> Result<Vec<Selector>, String> {
+        self.expect('[')?;
+        let mut selectors = vec![self.selector()?];
+        while self.eat(",") {
+            selectors.push(self.selector()?);
+        }
+        self.expect(']')?;
+        Ok(selectors)
+    }
+
+    fn selector(&mut self) -> Result<Selector, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('\'' | '"') => Ok(Selector::Name(self.string()?)),
+            Some('*') => {
+                self.position += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.position += 1;
+                Ok(Selector::Filter(self.or()?))
+            }
+            _ => {
+                let start = self.integer()?;
+                if !self.eat(":") {
+                    return match start {
+                        Some(index) => Ok(Selector::Index(index)),
+                        None => Err(self.error("expected a selector")),
+                    };
+                }
+                let end = self.integer()?;
+                let step = match self.eat(":") {
+                    true => self.integer()?,
+                    false => None,
+                };
+                Ok(Selector::Slice(start, end, step))
+            }
+        }
+    }
+
+    fn integer(&mut self) -> Result<Option<i64>, String> {
+        self.skip_whitespace();
+        let start = self.position;
+        if self.peek() == Some('-') {
+            self.position += 1;
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        match text.as_str() {
+            "" => Ok(None),
+            _ => text
+                .parse()
+                .map(Some)
+                .map_err(|_| self.error("invalid index")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let quote = self.peek().unwrap();
+        self.position += 1;
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(c) if c == quote => {
+                    self.position += 1;
+                    return Ok(s);
+                }
+                Some('\\') => {
+                    self.position += 1;
+                    match self.peek() {
+                        Some('n') => s.push('\n'),
+                        Some('t') => s.push('\t'),
+                        Some(c) => s.push(c),
+                        None => return Err(self.error("unterminated string")),
+                    }
+                    self.position += 1;
+                }
+                Some(c) => {
+                    s.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    fn or(&mut self) -> Result<Expression, String> {
+        let mut expression = self.and()?;
+        while self.eat("||") {
+            expression = Expression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> Result<Expression, String> {
+        let mut expression = self.unary()?;
+        while self.eat("&&") {
+            expression = Expression::And(Box::new(expression), Box::new(self.unary()?));
+        }
+        Ok(expression)
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        if self.eat("!") {
+            return Ok(Expression::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let expression = self.or()?;
+            self.expect(')')?;
+            return Ok(expression);
+        }
+        let left = self.operand()?;
+        let comparison = [
+            ("==", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+            ("<=", Comparison::LessOrEqual),
+            (">=", Comparison::GreaterOrEqual),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+        ]
+        .into_iter()
+        .find(|(operator, _)| self.eat(operator));
+        match (comparison, left) {
+            (Some((_, comparison)), left) => {
+                Ok(Expression::Compare(left, comparison, self.operand()?))
+            }
+            (None, Operand::Query(query)) => Ok(Expression::Exists(query)),
+            (None, Operand::Literal(_)) => Err(self.error("expected a comparison")),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c @ ('@' | '$')) => {
+                self.position += 1;
+                Ok(Operand::Query(Query {
+                    relative: c == '@',
+                    path: self.path()?,
+                }))
+            }
+            Some('\'' | '"') => Ok(Operand::Literal(Literal::String(self.string()?))),
+            _ => {
+                for (word, literal) in [
+                    ("true", Literal::Bool(true)),
+                    ("false", Literal::Bool(false)),
+                    ("null", Literal::Null),
+                ] {
+                    if self.eat(word) {
+                        return Ok(Operand::Literal(literal));
+                    }
+                }
+                let start = self.position;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+                {
+                    self.position += 1;
+                }
+                let text: String = self.chars[start..self.position].iter().collect();
+                text.parse()
+                    .map(|n| Operand::Literal(Literal::Number(n)))
+                    .map_err(|_| self.error("expected a value"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lenient_json::parse_lenient_json_string;
+    use crate::parse_json::parse_json_string;
+
+    const DOCUMENT: &str = r#"{
+        "store": {
+            "books": [
+                {"title": "A", "price": 8, "tags": ["old"]},
+                {"title": "B", "price": 12, "isbn": "1"},
+                {"title": "C", "price": 20, "isbn": "2"}
+            ],
+            "bike": {"color": "red", "price": 100}
+        },
+        "limit": 10,
+        "odd key": true
+    }"#;
+
+    /// Breadcrumbs of the nodes that `path` selects in `DOCUMENT`
+    fn select(path: &str) -> Vec<String> {
+        let json_path = JsonPath::parse(path).unwrap();
+        let items = parse_json_string(DOCUMENT).unwrap();
+        json_path
+            .evaluate(&items, &AtomicBool::new(false))
+            .into_iter()
+            .map(|line| tree::breadcrumbs(&items, line))
+            .collect()
+    }
+
+    #[test]
+    fn root() {
+        assert_eq!(select("$"), [""]);
+    }
+
+    #[test]
+    fn root_after_a_leading_comment() {
+        let items = parse_lenient_json_string("// header\n{\"a\": 1}", true);
+        assert!(matches!(items[0].value, JsonValueType::Comment(_)));
+        let select = |path: &str| {
+            JsonPath::parse(path)
+                .unwrap()
+                .evaluate(&items, &AtomicBool::new(false))
+        };
+        assert_eq!(select("$"), [1]);
+        assert_eq!(select("$.a"), [2]);
+    }
+
+    #[test]
+    fn cancelled_evaluation_stops() {
+        let items = parse_json_string(DOCUMENT).unwrap();
+        for path in ["$.store.*", "$..price", "$.store.books[?(@.price > 10)]"] {
+            let json_path = JsonPath::parse(path).unwrap();
+            assert!(json_path
+                .evaluate(&items, &AtomicBool::new(true))
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(select("$.limit"), ["limit"]);
+        assert_eq!(select("$.store.bike.color"), ["store ▶ bike ▶ color"]);
+        assert_eq!(select("$['store'][\"bike\"]"), ["store ▶ bike"]);
+        assert_eq!(select("$['odd key']"), ["odd key"]);
+        assert!(select("$.missing").is_empty());
+        // Names only select members of objects
+        assert!(select("$.store.books.title").is_empty());
+    }
+
+    #[test]
+    fn wildcards() {
+        assert_eq!(select("$.store.*"), ["store ▶ books", "store ▶ bike"]);
+        assert_eq!(
+            select("$.store.books[*].title"),
+            [
+                "store ▶ books ▶ [0] ▶ title",
+                "store ▶ books ▶ [1] ▶ title",
+                "store ▶ books ▶ [2] ▶ title"
+            ]
+        );
+    }
+
+    #[test]
+    fn indexes_and_slices() {
+        assert_eq!(
+            select("$.store.books[1].title"),
+            ["store ▶ books ▶ [1] ▶ title"]
+        );
+        assert_eq!(select("$.store.books[-1]"), ["store ▶ books ▶ [2]"]);
+        assert!(select("$.store.books[3]").is_empty());
+        assert!(select("$.store.books[-4]").is_empty());
+        assert_eq!(
+            select("$.store.books[1:]"),
+            ["store ▶ books ▶ [1]", "store ▶ books ▶ [2]"]
+        );
+        assert_eq!(select("$.store.books[:-2]"), ["store ▶ books ▶ [0]"]);
+        assert_eq!(
+            select("$.store.books[::2]"),
+            ["store ▶ books ▶ [0]", "store ▶ books ▶ [2]"]
+        );
+        // Results come in document order
+        assert_eq!(
+            select("$.store.books[::-1]"),
+            [
+                "store ▶ books ▶ [0]",
+                "store ▶ books ▶ [1]",
+                "store ▶ books ▶ [2]"
+            ]
+        );
+        assert!(select("$.store.books[2:1]").is_empty());
+    }
+
+    #[test]
+    fn unions() {
+        assert_eq!(
+            select("$.store.books[0,'x',2]"),
+            ["store ▶ books ▶ [0]", "store ▶ books ▶ [2]"]
+        );
+        assert_eq!(select("$['limit','odd key']"), ["limit", "odd key"]);
+    }
+
+    #[test]
+    fn recursive_descent() {
+        assert_eq!(
+            select("$..price"),
+            [
+                "store ▶ books ▶ [0] ▶ price",
+                "store ▶ books ▶ [1] ▶ price",
+                "store ▶ books ▶ [2] ▶ price",
+                "store ▶ bike ▶ price"
+            ]
+        );
+        assert_eq!(
+            select("$..[0]"),
+            ["store ▶ books ▶ [0]", "store ▶ books ▶ [0] ▶ tags ▶ [0]"]
+        );
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(select("$.store.books[?(@.price > 10)].title").len(), 2);
+        assert_eq!(
+            select("$.store.books[?(@.price >= 12 && @.price < 20)]"),
+            ["store ▶ books ▶ [1]"]
+        );
+        assert_eq!(
+            select("$.store.books[?(@.title == 'A' || @.isbn == \"2\")]"),
+            ["store ▶ books ▶ [0]", "store ▶ books ▶ [2]"]
+        );
+        assert_eq!(select("$.store.books[?(!@.isbn)]"), ["store ▶ books ▶ [0]"]);
+        assert_eq!(
+            select("$.store.books[?(@.isbn)]"),
+            ["store ▶ books ▶ [1]", "store ▶ books ▶ [2]"]
+        );
+        // `$` in a filter refers to the root
+        assert_eq!(
+            select("$.store.books[?(@.price < $.limit)]"),
+            ["store ▶ books ▶ [0]"]
+        );
+        // Values of different types are never equal
+        assert!(select("$.store.books[?(@.isbn == 1)]").is_empty());
+        assert_eq!(select("$.store.books[?(@.isbn != 1)]").len(), 3);
+    }
+
+    #[test]
+    fn filters_on_an_object_test_the_object_itself() {
+        assert_eq!(
+            select("$.store.bike[?(@.color == 'red')]"),
+            ["store ▶ bike"]
+        );
+        assert_eq!(
+            select("$.store.bike[?(@ == 'red')]"),
+            ["store ▶ bike ▶ color"]
+        );
+        assert!(select("$.store.bike[?(@.color == 'blue')]").is_empty());
+    }
+
+    #[test]
+    fn invalid_paths() {
+        let error = |path: &str| JsonPath::parse(path).err().unwrap();
+        assert_eq!(error("store"), "expected `$` at column 1");
+        assert_eq!(error("$.store]"), "unexpected `]` at column 8");
+        assert_eq!(error("$."), "expected a member name at column 3");
+        assert_eq!(error("$['store"), "unterminated string at column 9");
+        assert_eq!(error("$[?(@.price >)]"), "expected a value at column 14");
+        assert_eq!(error("$[?(1)]"), "expected a comparison at column 6");
+    }
+
+    #[test]
+    fn comparisons() {
+        let number = |n: f64| Some(Literal::Number(n));
+        assert!(compare(
+            number(1.0).as_ref(),
+            Comparison::Less,
+            number(2.0).as_ref()
+        ));
+        assert!(compare(
+            Some(&Literal::String("a".to_string())),
+            Comparison::Less,
+            Some(&Literal::String("b".to_string()))
+        ));
+        assert!(!compare(
+            Some(&Literal::Bool(false)),
+            Comparison::Less,
+            Some(&Literal::Bool(true))
+        ));
+        assert!(compare(None, Comparison::Equal, None));
+        assert!(compare(None, Comparison::NotEqual, Some(&Literal::Null)));
+    }
+}
@@ -0,0 +1,531 @@
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+
+use memmap2::Mmap;
+use serde_json::Value;
+
+use crate::json_item::{JsonItem, JsonValueType, UnloadedMembers};
+use crate::load::Progress;
+use crate::parse_json::ItemBuilder;
+
+/// Files at least this large are opened lazily even without `--lazy`
+pub const LAZY_THRESHOLD_BYTES: u64 = 1 << 30;
+
+/// Members of a container are read this many at a time
+const PAGE_SIZE: usize = 1000;
+
+/// Containers this large are indexed, smaller ones are quick to skip over
+const INDEXED_BYTES: usize = 4096;
+
+/// How often indexing reports how far it has got
+const PROGRESS_BYTES: usize = 1 << 20;
+
+/// A memory-mapped JSON file that is parsed on demand. The file is scanned once to index
+/// where its large containers are, without building any items. Containers start out
+/// collapsed; expanding one reads its first and last page of members, and the pages in
+/// between are read when they come into view. Memory use follows what has been looked
+/// at, not the file size.
+pub struct LazyDocument {
+    mmap: Mmap,
+    /// Where the root value ends
+    root_end: usize,
+    /// Large containers, ordered by offset
+    containers: Vec<IndexedContainer>,
+    /// Offsets of the first member of each page after the first, for all indexed
+    /// containers
+    page_offsets: Vec<usize>,
+}
+
+/// A container that is too large to skip over by reading it
+struct IndexedContainer {
+    start: usize,
+    end: usize,
+    len: usize,
+    /// Range of `page_offsets` that belongs to this container
+    pages: Range<usize>,
+}
+
+/// Location of one member of a container: the key (for objects) and the value
+struct Child {
+    key: Option<Range<usize>>,
+    value: Range<usize>,
+    len: usize,
+}
+
+impl LazyDocument {
+    pub fn open(file: &File, progress: &Progress) -> io::Result<LazyDocument> {
+        // The map is read-only; if another process truncates the file while we look at it,
+        // reads of the missing pages fail, like they would for any mmap-based viewer.
+        let mmap = unsafe { Mmap::map(file)? };
+        let mut lazy_document = LazyDocument {
+            mmap,
+            root_end: 0,
+            containers: vec![],
+            page_offsets: vec![],
+        };
+        lazy_document.build_index(progress);
+        Ok(lazy_document)
+    }
+
+    /// The first bytes of the file, for sniffing the format
+    pub fn prefix(&self, len: usize) -> &[u8] {
+        &self.mmap[..len.min(self.mmap.len())]
+    }
+
+    /// Whether there is more than whitespace after the root value, e.g. in JSON Lines
+    pub fn has_trailing_data(&self) -> bool {
+        skip_whitespace(&self.mmap, self.root_end) < self.mmap.len()
+    }
+
+    /// Items for the root value. A container root is expanded one level.
+    pub fn root_items(&self) -> Vec<JsonItem> {
+        let bytes = &self.mmap[..];
+        let start = skip_whitespace(bytes, 0);
+        let mut builder = ItemBuilder::new();
+        match bytes.get(start) {
+            Some(b'{') => builder.open_object(None),
+            Some(b'[') => builder.open_array(None),
+            _ => builder.value(None, scalar_value(&bytes[start..self.root_end])),
+        }
+        if builder.depth() > 0 {
+            let (_, len) = self.skip_value(start);
+            self.add_members(&mut builder, start, len);
+        }
+        builder.finish()
+    }
+
+    /// Materialize the children of a container that has not been loaded yet. Child
+    /// containers are returned collapsed and unloaded themselves.
+    pub fn load_children(&self, parent: &JsonItem) -> Vec<JsonItem> {
+        let mut builder = ItemBuilder::for_children_of(parent);
+        if let Some(span) = &parent.unloaded_span {
+            self.add_members(&mut builder, span.start, parent.len);
+        }
+        builder.finish_children()
+    }
+
+    /// Items to replace the unloaded members `placeholder` of `parent` with: the page at
+    /// the start (or end) of the members, and a placeholder for the rest if any
+    pub fn load_members(
+        &self,
+        parent: &JsonItem,
+        placeholder: &JsonItem,
+        from_end: bool,
+    ) -> Vec<JsonItem> {
+        let unloaded = match &placeholder.value {
+            JsonValueType::Unloaded(unloaded) => unloaded,
+            _ => return vec![],
+        };
+        let (container, members) = (unloaded.container, unloaded.members.clone());
+        let mut builder =
+            ItemBuilder::for_members_of(parent, members.start, placeholder.line_number);
+        let first_page = members.start / PAGE_SIZE;
+        let last_page = (members.end - 1) / PAGE_SIZE;
+        if from_end {
+            if last_page > first_page {
+                builder.unloaded_members(UnloadedMembers {
+                    container,
+                    members: members.start..last_page * PAGE_SIZE,
+                });
+            }
+            self.add_page(&mut builder, container, last_page);
+        } else {
+            self.add_page(&mut builder, container, first_page);
+            if last_page > first_page {
+                builder.unloaded_members(UnloadedMembers {
+                    container,
+                    members: (first_page + 1) * PAGE_SIZE..members.end,
+                });
+            }
+        }
+        builder.finish_children()
+    }
+
+    /// Parse the container at `start` as a whole, for when all of it is needed
+    pub fn value(&self, start: usize) -> Result<Value, String> {
+        let (end, _) = self.skip_value(start);
+        serde_json::from_slice(&self.mmap[start..end])
+            .map_err(|error| format!("Invalid JSON in the container at byte {}: {}", start, error))
+    }
+
+    /// Size of the file in bytes
+    pub fn size(&self) -> usize {
+        self.mmap.len()
+    }
+
+    /// The first and the last page of the members of the container at `start`, with a
+    /// placeholder for the ones in between
+    fn add_members(&self, builder: &mut ItemBuilder, start: usize, len: usize) {
+        let num_pages = len.div_ceil(PAGE_SIZE).max(1);
+        self.add_page(builder, start, 0);
+        if num_pages > 2 {
+            builder.unloaded_members(UnloadedMembers {
+                container: start,
+                members: PAGE_SIZE..(num_pages - 1) * PAGE_SIZE,
+            });
+        }
+        if num_pages > 1 {
+            self.add_page(builder, start, num_pages - 1);
+        }
+    }
+
+    fn add_page(&self, builder: &mut ItemBuilder, container: usize, page: usize) {
+        let bytes = &self.mmap[..];
+        let start = match page {
+            0 => container + 1,
+            _ => match self.indexed(container) {
+                Some(indexed) => self.page_offsets[indexed.pages.start + page - 1],
+                None => return,
+            },
+        };
+        let (children, error) = self.scan_members(container, start);
+        for child in children {
+            let name = child.key.map(|key| {
+                serde_json::from_slice::<String>(&bytes[key.clone()])
+                    .unwrap_or_else(|_| lossy(&bytes[key]))
+            });
+            match bytes[child.value.start] {
+                b'{' => builder.unloaded(name, JsonValueType::Object, child.value, child.len),
+                b'[' => builder.unloaded(name, JsonValueType::Array, child.value, child.len),
+                _ => builder.value(name, scalar_value(&bytes[child.value])),
+            }
+        }
+        if let Some(offset) = error {
+            builder.error(format!("invalid JSON at byte {}", offset));
+        }
+    }
+
+    fn indexed(&self, start: usize) -> Option<&IndexedContainer> {
+        self.containers
+            .binary_search_by_key(&start, |container| container.start)
+            .ok()
+            .map(|index| &self.containers[index])
+    }
+
+    /// Returns the end of the value starting at `start` and, for containers, the number
+    /// of members. Indexed containers are looked up, others are skipped over.
+    fn skip_value(&self, start: usize) -> (usize, usize) {
+        match self.indexed(start) {
+            Some(indexed) => (indexed.end, indexed.len),
+            None => skip_value(&self.mmap, start),
+        }
+    }
+
+    /// Find up to a page of members of the container at `container`, starting at the
+    /// member at `start`. On malformed input, the members found so far are returned
+    /// together with the offset of the problem.
+    fn scan_members(&self, container: usize, start: usize) -> (Vec<Child>, Option<usize>) {
+        let bytes = &self.mmap[..];
+        let is_object = bytes[container] == b'{';
+        let close = if is_object { b'}' } else { b']' };
+        let mut children = Vec::new();
+        let mut i = skip_whitespace(bytes, start);
+        if bytes.get(i) == Some(&close) {
+            return (children, None);
+        }
+        while children.len() < PAGE_SIZE {
+            let key = if is_object {
+                if bytes.get(i) != Some(&b'"') {
+                    return (children, Some(i));
+                }
+                let key_end = skip_string(bytes, i);
+                let key = i..key_end;
+                i = skip_whitespace(bytes, key_end);
+                if bytes.get(i) != Some(&b':') {
+                    return (children, Some(i));
+                }
+                i = skip_whitespace(bytes, i + 1);
+                Some(key)
+            } else {
+                None
+            };
+            if i >= bytes.len() {
+                return (children, Some(i));
+            }
+            let (end, len) = self.skip_value(i);
+            children.push(Child {
+                key,
+                value: i..end,
+                len,
+            });
+            i = skip_whitespace(bytes, end);
+            match bytes.get(i) {
+                Some(b',') => i = skip_whitespace(bytes, i + 1),
+                Some(c) if *c == close => return (children, None),
+                _ => return (children, Some(i)),
+            }
+        }
+        (children, None)
+    }
+
+    /// Scan the root value once, recording the large containers and where their pages
+    /// start. Strings are skipped as a whole so that brackets in them don't count.
+    fn build_index(&mut self, progress: &Progress) {
+        struct Open {
+            start: usize,
+            len: usize,
+            page_offsets: Vec<usize>,
+            /// Whether the next value starts a member, as opposed to following a key
+            expects_member: bool,
+        }
+        let bytes = &self.mmap[..];
+        let start = skip_whitespace(bytes, 0);
+        if !matches!(bytes.get(start), Some(b'{' | b'[')) {
+            self.root_end = skip_value(bytes, start).0;
+            return;
+        }
+        let mut containers = vec![];
+        let mut page_offsets = vec![];
+        let mut stack: Vec<Open> = vec![];
+        let mut i = start;
+        let mut reported = 0;
+        // Count a member of the innermost container if one starts at `i`
+        let member_at = |stack: &mut Vec<Open>, i: usize| {
+            if let Some(open) = stack.last_mut().filter(|open| open.expects_member) {
+                if open.len > 0 && open.len % PAGE_SIZE == 0 {
+                    open.page_offsets.push(i);
+                }
+                open.len += 1;
+                open.expects_member = false;
+            }
+        };
+        while i < bytes.len() {
+            if i - reported >= PROGRESS_BYTES {
+                progress.advance(i - reported);
+                reported = i;
+            }
+            match bytes[i] {
+                b'"' => {
+                    member_at(&mut stack, i);
+                    i = skip_string(bytes, i);
+                    continue;
+                }
+                b'{' | b'[' => {
+                    member_at(&mut stack, i);
+                    stack.push(Open {
+                        start: i,
+                        len: 0,
+                        page_offsets: vec![],
+                        expects_member: true,
+                    });
+                }
+                b'}' | b']' => {
+                    if let Some(open) = stack.pop() {
+                        if i + 1 - open.start >= INDEXED_BYTES || open.len > PAGE_SIZE {
+                            let first_page = page_offsets.len();
+                            page_offsets.extend(open.page_offsets);
+                            containers.push(IndexedContainer {
+                                start: open.start,
+                                end: i + 1,
+                                len: open.len,
+                                pages: first_page..page_offsets.len(),
+                            });
+                        }
+                    }
+                    if stack.is_empty() {
+                        i += 1;
+                        break;
+                    }
+                }
+                b',' => {
+                    if let Some(open) = stack.last_mut() {
+                        open.expects_member = true;
+                    }
+                }
+                b' ' | b'\t' | b'\n' | b'\r' | b':' => {}
+                _ => member_at(&mut stack, i),
+            }
+            i += 1;
+        }
+        // Containers that are still open at the end of a truncated file end there
+        for open in stack.into_iter().rev() {
+            let first_page = page_offsets.len();
+            page_offsets.extend(open.page_offsets);
+            containers.push(IndexedContainer {
+                start: open.start,
+                end: bytes.len(),
+                len: open.len,
+                pages: first_page..page_offsets.len(),
+            });
+        }
+        // Containers are recorded as they close, inner ones first
+        containers.sort_unstable_by_key(|container| container.start);
+        self.root_end = i;
+        self.containers = containers;
+        self.page_offsets = page_offsets;
+    }
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).to_string()
+}
+
+fn scalar_value(bytes: &[u8]) -> JsonValueType {
+    match serde_json::from_slice::<Value>(bytes) {
+        Ok(Value::Number(n)) => JsonValueType::Number(n),
+        Ok(Value::String(s)) => JsonValueType::String(s),
+        Ok(Value::Bool(b)) => JsonValueType::Bool(b),
+        Ok(Value::Null) => JsonValueType::Null,
+        Ok(_) => JsonValueType::Error("unexpected container".to_string()),
+        Err(err) => JsonValueType::Error(format!("invalid value `{}`: {}", lossy(bytes), err)),
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && matches!(bytes[i], b' ' | b'\t' | b'\n' | b'\r') {
+        i += 1;
+    }
+    i
+}
+
+/// `i` is at the opening quote; returns the index after the closing quote
+fn skip_string(bytes: &[u8], mut i: usize) -> usize {
+    i += 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => return i + 1,
+            b'\\' => i += 2,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Returns the end of the value starting at `i` and, for containers, the number of
+/// children. Nested containers are skipped by counting brackets, without parsing.
+fn skip_value(bytes: &[u8], start: usize) -> (usize, usize) {
+    match bytes.get(start) {
+        Some(b'{' | b'[') => {}
+        Some(b'"') => return (skip_string(bytes, start), 0),
+        _ => {
+            let mut i = start;
+            while i < bytes.len()
+                && !matches!(bytes[i], b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r')
+            {
+                i += 1;
+            }
+            return (i, 0);
+        }
+    }
+    let first = skip_whitespace(bytes, start + 1);
+    let mut len = match bytes.get(first) {
+        Some(b'}' | b']') | None => 0,
+        _ => 1,
+    };
+    let mut depth = 0;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                i = skip_string(bytes, i);
+                continue;
+            }
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return (i + 1, len);
+                }
+            }
+            b',' if depth == 1 => len += 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    (bytes.len(), len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(name: &str, text: &str) -> LazyDocument {
+        let path = std::env::temp_dir().join(format!("jex-{}-{}.json", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        let file = File::open(&path).unwrap();
+        let lazy_document = LazyDocument::open(&file, &Progress::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        lazy_document
+    }
+
+    /// `[0, 1, …]` with `len` numbers
+    fn numbers(len: usize) -> String {
+        let numbers: Vec<String> = (0..len).map(|i| i.to_string()).collect();
+        format!("[{}]", numbers.join(", "))
+    }
+
+    fn unloaded_members(item: &JsonItem) -> Range<usize> {
+        match &item.value {
+            JsonValueType::Unloaded(unloaded) => unloaded.members.clone(),
+            _ => panic!("not a placeholder: {}", item.value_str),
+        }
+    }
+
+    /// The members among `items`, as `(index_in_parent, value)`
+    fn members(items: &[JsonItem]) -> Vec<(usize, String)> {
+        items
+            .iter()
+            .filter(|item| matches!(item.value, JsonValueType::Number(_)))
+            .map(|item| (item.index_in_parent, item.value_str.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn the_root_has_its_first_and_last_page() {
+        let document = open("root", &numbers(3500));
+        let items = document.root_items();
+        // `[`, 1000 members, a placeholder, 500 members, `]`
+        assert_eq!(items.len(), 1503);
+        assert_eq!(items[0].len, 3500);
+        assert_eq!(unloaded_members(&items[1001]), 1000..3000);
+        let expected: Vec<(usize, String)> = (0..1000)
+            .chain(3000..3500)
+            .map(|i| (i, i.to_string()))
+            .collect();
+        assert_eq!(members(&items), expected);
+        assert_eq!(items[1000].next_sibling, Some(1001));
+        assert_eq!(items[1001].next_sibling, Some(1002));
+        assert_eq!(items[1502].bracket, Some(0));
+    }
+
+    #[test]
+    fn members_are_read_next_to_where_the_placeholder_was_entered() {
+        let document = open("members", &numbers(3500));
+        let items = document.root_items();
+        let placeholder = &items[1001];
+
+        let from_start = document.load_members(&items[0], placeholder, false);
+        assert_eq!(from_start.len(), 1001);
+        assert_eq!(from_start[0].line_number, 1001);
+        assert_eq!(from_start[0].parent, Some(0));
+        assert_eq!(unloaded_members(&from_start[1000]), 2000..3000);
+        let expected: Vec<(usize, String)> = (1000..2000).map(|i| (i, i.to_string())).collect();
+        assert_eq!(members(&from_start), expected);
+
+        let from_end = document.load_members(&items[0], placeholder, true);
+        assert_eq!(from_end.len(), 1001);
+        assert_eq!(unloaded_members(&from_end[0]), 1000..2000);
+        assert_eq!(from_end[1].index_in_parent, 2000);
+        let expected: Vec<(usize, String)> = (2000..3000).map(|i| (i, i.to_string())).collect();
+        assert_eq!(members(&from_end), expected);
+    }
+
+    #[test]
+    fn unloaded_containers_read_their_children() {
+        let document = open("children", r#"{"a": {"b": [1, 2]}, "c": 3}"#);
+        let items = document.root_items();
+        assert_eq!(items[1].name.as_deref(), Some("a"));
+        assert!(items[1].unloaded_span.is_some());
+        let children = document.load_children(&items[1]);
+        // `b` is unloaded itself, with its closing bracket
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].name.as_deref(), Some("b"));
+        assert_eq!(children[0].parent, Some(1));
+        assert_eq!(children[0].line_number, 2);
+        assert_eq!(children[0].len, 2);
+        assert!(children[0].unloaded_span.is_some());
+        assert!(children[1].value == JsonValueType::ArrayEnd);
+        assert_eq!(children[1].bracket, Some(2));
+    }
+}
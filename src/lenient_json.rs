@@ -0,0 +1,812 @@
+use serde_json::Number;
+
+use crate::json_item::{JsonItem, JsonValueType};
+use crate::parse_json::{float_value, ItemBuilder};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Expect {
+    Value,
+    Key,
+    KeyOrClose,
+    Colon,
+    CommaOrClose,
+    ValueOrClose,
+    Done,
+}
+
+pub struct SyntaxError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A JSON parser that never gives up. Wherever the input is malformed, an error item is
+/// inserted and parsing resumes at the next sensible position. Containers that are still
+/// open at the end of the input are closed, so truncated documents can be browsed.
+///
+/// In relaxed mode it also accepts JSON5/JSONC: comments (kept as comment items),
+/// trailing commas, unquoted keys, single-quoted strings and JSON5 number syntax.
+struct Parser<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    line: usize,
+    line_start: usize,
+    builder: ItemBuilder,
+    pending_name: Option<String>,
+    relaxed: bool,
+    first_error: Option<SyntaxError>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str, relaxed: bool) -> Parser<'a> {
+        Parser {
+            text,
+            bytes: text.as_bytes(),
+            pos: 0,
+            line: 1,
+            line_start: 0,
+            builder: ItemBuilder::new(),
+            pending_name: None,
+            relaxed,
+            first_error: None,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.pos += 1;
+            if c == b'\n' {
+                self.line += 1;
+                self.line_start = self.pos;
+            }
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\n' | b'\r') => self.bump(),
+                Some(b'/') if self.relaxed => match self.bytes.get(self.pos + 1) {
+                    Some(b'/') => self.parse_line_comment(),
+                    Some(b'*') => self.parse_block_comment(),
+                    _ => return,
+                },
+                _ => return,
+            }
+        }
+    }
+
+    fn parse_line_comment(&mut self) {
+        let start = self.pos + 2;
+        while !matches!(self.peek(), None | Some(b'\n')) {
+            self.pos += 1;
+        }
+        let comment = self.text[start..self.pos].trim_end_matches('\r').trim();
+        self.builder.comment(comment.to_string());
+    }
+
+    /// Every line of a block comment becomes a separate comment item
+    fn parse_block_comment(&mut self) {
+        let location = self.location();
+        let start = self.pos + 2;
+        let end = match self.text[start..].find("*/") {
+            Some(length) => start + length,
+            None => self.bytes.len(),
+        };
+        for line in self.text[start..end].lines() {
+            let line = line.trim().trim_start_matches('*').trim();
+            if !line.is_empty() {
+                self.builder.comment(line.to_string());
+            }
+        }
+        while self.pos < end {
+            self.bump();
+        }
+        match self.peek() {
+            Some(_) => {
+                self.bump();
+                self.bump();
+            }
+            None => self.error_at("unterminated comment", location),
+        }
+    }
+
+    /// Line and (1-based, byte) column of the current position
+    fn location(&self) -> (usize, usize) {
+        (self.line, self.pos - self.line_start + 1)
+    }
+
+    fn error(&mut self, message: &str) {
+        self.error_at(message, self.location());
+    }
+
+    fn error_at(&mut self, message: &str, (line, column): (usize, usize)) {
+        self.builder
+            .error(format!("{} at line {} column {}", message, line, column));
+        if self.first_error.is_none() {
+            self.first_error = Some(SyntaxError {
+                message: message.to_string(),
+                line,
+                column,
+            });
+        }
+    }
+
+    /// Skip the current character (a whole UTF-8 sequence)
+    fn skip_char(&mut self) {
+        self.bump();
+        while self.pos < self.bytes.len() && !self.text.is_char_boundary(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn current_char(&self) -> String {
+        self.text[self.pos..]
+            .chars()
+            .next()
+            .map(|c| c.to_string())
+            .unwrap_or_default()
+    }
+
+    fn in_array(&self) -> bool {
+        self.builder.is_in_array()
+    }
+
+    fn after_value(&self) -> Expect {
+        match self.builder.depth() {
+            0 => Expect::Done,
+            _ => Expect::CommaOrClose,
+        }
+    }
+
+    fn parse(&mut self) {
+        // A byte order mark is not part of the document, e.g. from Windows editors
+        if self.text.starts_with('\u{FEFF}') {
+            self.pos = '\u{FEFF}'.len_utf8();
+            self.line_start = self.pos;
+        }
+        let mut expect = Expect::Value;
+        loop {
+            self.skip_whitespace();
+            let c = match self.peek() {
+                Some(c) => c,
+                None => {
+                    match (expect, self.builder.depth()) {
+                        (Expect::Done, _) => {}
+                        (_, 0) => self.error("unexpected end of input"),
+                        _ => self.error("unexpected end of input, closing open containers"),
+                    }
+                    break;
+                }
+            };
+            expect = match (expect, c) {
+                (Expect::Done, _) => {
+                    self.error("trailing characters");
+                    break;
+                }
+                (Expect::KeyOrClose, b'}') | (Expect::ValueOrClose, b']') => {
+                    self.bump();
+                    self.builder.close();
+                    self.after_value()
+                }
+                (Expect::CommaOrClose, b'}') if !self.in_array() => {
+                    self.bump();
+                    self.builder.close();
+                    self.after_value()
+                }
+                (Expect::CommaOrClose, b']') if self.in_array() => {
+                    self.bump();
+                    self.builder.close();
+                    self.after_value()
+                }
+                (Expect::CommaOrClose, b',') => {
+                    self.bump();
+                    match (self.in_array(), self.relaxed) {
+                        (true, false) => Expect::Value,
+                        (false, false) => Expect::Key,
+                        (true, true) => Expect::ValueOrClose,
+                        (false, true) => Expect::KeyOrClose,
+                    }
+                }
+                (Expect::CommaOrClose, b'"') if !self.in_array() => {
+                    // Assume a forgotten comma between members
+                    self.error("expected `,` or `}`");
+                    Expect::KeyOrClose
+                }
+                (Expect::CommaOrClose, _) => {
+                    let expected = match self.in_array() {
+                        true => "expected `,` or `]`",
+                        false => "expected `,` or `}`",
+                    };
+                    self.error(expected);
+                    match c {
+                        b'{' | b'[' | b'"' | b'-' | b'0'..=b'9' if self.in_array() => Expect::Value,
+                        _ => {
+                            self.skip_char();
+                            Expect::CommaOrClose
+                        }
+                    }
+                }
+                (Expect::Key, b'}') => {
+                    self.error("trailing comma");
+                    self.bump();
+                    self.builder.close();
+                    self.after_value()
+                }
+                (Expect::Key | Expect::KeyOrClose, b'"') => {
+                    self.pending_name = Some(self.parse_string());
+                    Expect::Colon
+                }
+                (Expect::Key | Expect::KeyOrClose, b'\'') if self.relaxed => {
+                    self.pending_name = Some(self.parse_string());
+                    Expect::Colon
+                }
+                (Expect::Key | Expect::KeyOrClose, _) if self.relaxed && is_identifier(c) => {
+                    self.pending_name = Some(self.parse_identifier());
+                    Expect::Colon
+                }
+                (Expect::Key | Expect::KeyOrClose, _) => {
+                    self.error("expected a key");
+                    self.skip_to_member_end()
+                }
+                (Expect::Colon, b':') => {
+                    self.bump();
+                    Expect::Value
+                }
+                (Expect::Colon, _) => {
+                    self.error("expected `:`");
+                    Expect::Value
+                }
+                (Expect::Value | Expect::ValueOrClose, _) => self.parse_value(c),
+            };
+        }
+    }
+
+    fn parse_value(&mut self, c: u8) -> Expect {
+        let name = self.pending_name.take();
+        match c {
+            b'{' => {
+                self.bump();
+                self.builder.open_object(name);
+                Expect::KeyOrClose
+            }
+            b'[' => {
+                self.bump();
+                self.builder.open_array(name);
+                Expect::ValueOrClose
+            }
+            b'"' | b'\'' if c == b'"' || self.relaxed => {
+                let s = self.parse_string();
+                self.builder.value(name, JsonValueType::String(s));
+                self.after_value()
+            }
+            b'}' | b']' | b',' | b':' => {
+                self.error(&format!("unexpected `{}`", c as char));
+                let closes_container =
+                    self.builder.depth() > 0 && (c == b']') == self.in_array() && c != b',';
+                match (c, closes_container) {
+                    // A missing value: let the bracket close the container
+                    (b'}' | b']', true) => Expect::CommaOrClose,
+                    _ if self.builder.depth() == 0 => {
+                        self.bump();
+                        self.skip_to_root()
+                    }
+                    _ => {
+                        self.bump();
+                        self.pending_name = name;
+                        Expect::Value
+                    }
+                }
+            }
+            _ => {
+                let location = self.location();
+                let token = self.parse_token();
+                let value = match self.relaxed {
+                    true => json5_literal(&token),
+                    false => literal(&token),
+                };
+                match value {
+                    Some(value) => {
+                        self.builder.value(name, value);
+                        return self.after_value();
+                    }
+                    None if token.is_empty() => {
+                        let message = format!("unexpected `{}`", self.current_char());
+                        self.error_at(&message, location);
+                        self.skip_char();
+                    }
+                    None => self.error_at(&format!("invalid value `{}`", token), location),
+                }
+                match self.builder.depth() {
+                    // Junk instead of the root value
+                    0 => self.skip_to_root(),
+                    _ => self.after_value(),
+                }
+            }
+        }
+    }
+
+    /// A bare word: number, `true`, `false`, `null` or garbage
+    fn parse_token(&mut self) -> String {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || matches!(c, b'+' | b'-' | b'.' | b'_') {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        self.text[start..self.pos].to_string()
+    }
+
+    /// An unquoted JSON5 key
+    fn parse_identifier(&mut self) -> String {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if is_identifier(c) || c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.text[start..self.pos].to_string()
+    }
+
+    /// Parse a string starting at the opening quote. An unterminated string runs to the
+    /// end of the line.
+    fn parse_string(&mut self) -> String {
+        let location = self.location();
+        let quote = self.peek();
+        self.bump();
+        let mut output = String::new();
+        loop {
+            let start = self.pos;
+            while let Some(c) = self.peek() {
+                if Some(c) == quote || c == b'\\' || c == b'\n' {
+                    break;
+                }
+                self.pos += 1;
+            }
+            output.push_str(&self.text[start..self.pos]);
+            match self.peek() {
+                Some(b'\\') => {
+                    self.bump();
+                    self.parse_escape(&mut output);
+                }
+                Some(b'\n') | None => {
+                    self.error_at("unterminated string", location);
+                    return output;
+                }
+                Some(_) => {
+                    self.bump();
+                    return output;
+                }
+            }
+        }
+    }
+
+    fn parse_escape(&mut self, output: &mut String) {
+        let escaped = match self.peek() {
+            Some(b'n') => '\n',
+            Some(b't') => '\t',
+            Some(b'r') => '\r',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'\n') if self.relaxed => {
+                // JSON5 line continuation
+                self.bump();
+                return;
+            }
+            Some(b'u') => {
+                self.bump();
+                output.push(self.parse_unicode_escape());
+                return;
+            }
+            Some(_) => {
+                // `\"`, `\\`, `\/` and invalid escapes keep the escaped character
+                output.push_str(&self.current_char());
+                self.skip_char();
+                return;
+            }
+            None => return,
+        };
+        self.bump();
+        output.push(escaped);
+    }
+
+    /// The `XXXX` part of `\uXXXX`, combining UTF-16 surrogate pairs
+    fn parse_unicode_escape(&mut self) -> char {
+        let code = match self.parse_hex4() {
+            Some(high @ 0xD800..=0xDBFF) if self.text[self.pos..].starts_with("\\u") => {
+                self.pos += 2;
+                match self.parse_hex4() {
+                    Some(low @ 0xDC00..=0xDFFF) => {
+                        Some(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+                    }
+                    _ => None,
+                }
+            }
+            code => code,
+        };
+        code.and_then(char::from_u32).unwrap_or('\u{FFFD}')
+    }
+
+    fn parse_hex4(&mut self) -> Option<u32> {
+        let hex = self.text.get(self.pos..self.pos + 4)?;
+        let code = u32::from_str_radix(hex, 16).ok()?;
+        self.pos += 4;
+        Some(code)
+    }
+
+    /// After junk in front of the root value, e.g. HTTP headers, skip ahead to the next
+    /// `{` or `[`. Without one there is nothing more to show.
+    fn skip_to_root(&mut self) -> Expect {
+        while let Some(c) = self.peek() {
+            match c {
+                b'{' | b'[' => return Expect::Value,
+                _ => self.skip_char(),
+            }
+        }
+        Expect::Done
+    }
+
+    /// After a broken object member, skip ahead to the next `,`, the closing `}` or the
+    /// next line, whichever comes first
+    fn skip_to_member_end(&mut self) -> Expect {
+        while let Some(c) = self.peek() {
+            match c {
+                b',' | b'}' => return Expect::CommaOrClose,
+                b'\n' => return Expect::KeyOrClose,
+                _ => self.skip_char(),
+            }
+        }
+        Expect::CommaOrClose
+    }
+}
+
+fn literal(token: &str) -> Option<JsonValueType> {
+    match token {
+        "true" => Some(JsonValueType::Bool(true)),
+        "false" => Some(JsonValueType::Bool(false)),
+        "null" => Some(JsonValueType::Null),
+        _ => token.parse::<Number>().ok().map(JsonValueType::Number),
+    }
+}
+
+/// JSON5 numbers: hexadecimal, leading `+`, leading or trailing decimal points, and
+/// `Infinity` and `NaN`
+fn json5_literal(token: &str) -> Option<JsonValueType> {
+    let (sign, unsigned) = match token.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", token.strip_prefix('+').unwrap_or(token)),
+    };
+    let special = match unsigned {
+        "Infinity" => Some(f64::INFINITY),
+        "NaN" => Some(f64::NAN),
+        _ => None,
+    };
+    if let Some(special) = special {
+        return Some(float_value(if sign == "-" { -special } else { special }));
+    }
+    if let Some(hex) = unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    {
+        // `from_str_radix` would accept another sign after the `0x`
+        if !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let value = i64::from_str_radix(hex, 16).ok()?;
+        let value = if sign == "-" { -value } else { value };
+        return Some(JsonValueType::Number(Number::from(value)));
+    }
+    let mut number = unsigned.to_string();
+    if number.starts_with('.') {
+        number.insert(0, '0');
+    }
+    if number.ends_with('.') {
+        number.push('0');
+    }
+    literal(&format!("{}{}", sign, number)).or_else(|| literal(token))
+}
+
+fn is_identifier(c: u8) -> bool {
+    c.is_ascii_alphabetic() || matches!(c, b'_' | b'$') || !c.is_ascii()
+}
+
+/// Parse as much of the text as possible, marking the places where recovery happened
+pub fn parse_lenient_json_string(json_string: &str, relaxed: bool) -> Vec<JsonItem> {
+    let mut parser = Parser::new(json_string, relaxed);
+    parser.parse();
+    parser.builder.finish()
+}
+
+/// Parse JSON5/JSONC, failing on the first syntax error
+pub fn parse_json5_string(json_string: &str) -> Result<Vec<JsonItem>, SyntaxError> {
+    let mut parser = Parser::new(json_string, true);
+    parser.parse();
+    match parser.first_error {
+        Some(error) => Err(error),
+        None => Ok(parser.builder.finish()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One line per item, e.g. `name: "text"`, `[`, `error: ...` or `// comment`
+    fn summary(items: &[JsonItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| {
+                let value = match &item.value {
+                    JsonValueType::Object => "{".to_string(),
+                    JsonValueType::ObjectEnd => "}".to_string(),
+                    JsonValueType::Array => "[".to_string(),
+                    JsonValueType::ArrayEnd => "]".to_string(),
+                    JsonValueType::Null => "null".to_string(),
+                    JsonValueType::String(s) => format!("{:?}", s),
+                    JsonValueType::Error(e) => format!("error: {}", e),
+                    JsonValueType::Comment(c) => format!("// {}", c),
+                    _ => item.value_str.clone(),
+                };
+                match &item.name {
+                    Some(name) => format!("{}: {}", name, value),
+                    None => value,
+                }
+            })
+            .collect()
+    }
+
+    fn lenient(text: &str) -> Vec<String> {
+        summary(&parse_lenient_json_string(text, false))
+    }
+
+    fn relaxed(text: &str) -> Vec<String> {
+        summary(&parse_lenient_json_string(text, true))
+    }
+
+    #[test]
+    fn valid_json_is_unchanged() {
+        assert_eq!(
+            lenient(r#"{"a": [1, -2.5e3, "x\né😀", true, null]}"#),
+            [
+                "{",
+                "a: [",
+                "1",
+                "-2500.0",
+                "\"x\\né😀\"",
+                "true",
+                "null",
+                "]",
+                "}"
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_comma_between_members() {
+        assert_eq!(
+            lenient(r#"{"a": 1 "b": 2}"#),
+            [
+                "{",
+                "a: 1",
+                "error: expected `,` or `}` at line 1 column 9",
+                "b: 2",
+                "}"
+            ]
+        );
+        assert_eq!(
+            lenient("[1 2]"),
+            [
+                "[",
+                "1",
+                "error: expected `,` or `]` at line 1 column 4",
+                "2",
+                "]"
+            ]
+        );
+    }
+
+    #[test]
+    fn trailing_comma_is_an_error_unless_relaxed() {
+        assert_eq!(
+            lenient(r#"{"a": 1,}"#),
+            ["{", "a: 1", "error: trailing comma at line 1 column 9", "}"]
+        );
+        assert_eq!(
+            lenient("[1,]"),
+            ["[", "1", "error: unexpected `]` at line 1 column 4", "]"]
+        );
+        assert_eq!(relaxed(r#"{"a": [1,],}"#), ["{", "a: [", "1", "]", "}"]);
+    }
+
+    #[test]
+    fn missing_value_and_colon() {
+        assert_eq!(
+            lenient(r#"{"a": }"#),
+            ["{", "error: unexpected `}` at line 1 column 7", "}"]
+        );
+        assert_eq!(
+            lenient(r#"{"a" 1}"#),
+            ["{", "error: expected `:` at line 1 column 6", "a: 1", "}"]
+        );
+    }
+
+    #[test]
+    fn invalid_values_and_keys() {
+        assert_eq!(
+            lenient("[1, tru, 3]"),
+            [
+                "[",
+                "1",
+                "error: invalid value `tru` at line 1 column 5",
+                "3",
+                "]"
+            ]
+        );
+        assert_eq!(
+            lenient("{a: 1, \"b\": 2}"),
+            ["{", "error: expected a key at line 1 column 2", "b: 2", "}"]
+        );
+    }
+
+    #[test]
+    fn truncated_input_closes_open_containers() {
+        assert_eq!(
+            lenient(r#"{"a": [1, 2"#),
+            [
+                "{",
+                "a: [",
+                "1",
+                "2",
+                "error: unexpected end of input, closing open containers at line 1 column 12",
+                "]",
+                "}"
+            ]
+        );
+        assert_eq!(
+            lenient("[\"abc\n]"),
+            [
+                "[",
+                "error: unterminated string at line 1 column 2",
+                "\"abc\"",
+                "]"
+            ]
+        );
+        assert_eq!(
+            lenient(""),
+            ["error: unexpected end of input at line 1 column 1"]
+        );
+    }
+
+    #[test]
+    fn trailing_characters() {
+        assert_eq!(
+            lenient("{} x"),
+            ["{", "}", "error: trailing characters at line 1 column 4"]
+        );
+    }
+
+    #[test]
+    fn byte_order_mark_is_skipped() {
+        assert_eq!(lenient("\u{FEFF}{\"a\": 1}"), ["{", "a: 1", "}"]);
+        assert_eq!(
+            lenient("\u{FEFF}{\"a\" 1}"),
+            ["{", "error: expected `:` at line 1 column 6", "a: 1", "}"]
+        );
+    }
+
+    #[test]
+    fn junk_before_the_root_value_is_skipped() {
+        assert_eq!(
+            lenient("HTTP/1.1 200 OK\nContent-Type: application/json\n\n{\"a\": [1]}"),
+            [
+                "error: invalid value `HTTP` at line 1 column 1",
+                "{",
+                "a: [",
+                "1",
+                "]",
+                "}"
+            ]
+        );
+        assert_eq!(
+            lenient(")]}',\n[1]"),
+            ["error: unexpected `)` at line 1 column 1", "[", "1", "]"]
+        );
+        assert_eq!(
+            lenient("garbage"),
+            ["error: invalid value `garbage` at line 1 column 1"]
+        );
+    }
+
+    #[test]
+    fn errors_are_not_counted_as_children() {
+        let items = parse_lenient_json_string("[1 2]", false);
+        assert_eq!(items[0].len, 2);
+        assert_eq!(items[2].index_in_parent, 0);
+        assert_eq!(items[3].index_in_parent, 1);
+    }
+
+    #[test]
+    fn comments_only_when_relaxed() {
+        assert_eq!(
+            relaxed("// header\n{\"a\": 1, /* first\n * second */ \"b\": 2}"),
+            [
+                "// header",
+                "{",
+                "a: 1",
+                "// first",
+                "// second",
+                "b: 2",
+                "}"
+            ]
+        );
+        let strict = lenient("[1, // no\n2]");
+        assert_eq!(strict[2], "error: unexpected `/` at line 1 column 5");
+        assert!(!strict.iter().any(|line| line.starts_with("//")));
+        assert_eq!(strict[strict.len() - 2..], ["2", "]"]);
+        assert_eq!(
+            relaxed("[1 /* open"),
+            [
+                "[",
+                "1",
+                "// open",
+                "error: unterminated comment at line 1 column 4",
+                "error: unexpected end of input, closing open containers at line 1 column 11",
+                "]"
+            ]
+        );
+    }
+
+    #[test]
+    fn json5_keys_strings_and_numbers() {
+        assert_eq!(
+            relaxed("{unquoted: 'single \"quoted\"', $id: 'a\\\nb'}"),
+            [
+                "{",
+                "unquoted: \"single \\\"quoted\\\"\"",
+                "$id: \"ab\"",
+                "}"
+            ]
+        );
+        assert_eq!(
+            relaxed("[0x1F, -0x10, +1, .5, 5., -.5]"),
+            ["[", "31", "-16", "1", "0.5", "5.0", "-0.5", "]"]
+        );
+    }
+
+    #[test]
+    fn json5_infinity_and_nan_become_strings() {
+        assert_eq!(
+            relaxed("[Infinity, -Infinity, +Infinity, NaN, -NaN]"),
+            ["[", "\"inf\"", "\"-inf\"", "\"inf\"", "\"NaN\"", "\"NaN\"", "]"]
+        );
+        assert!(parse_json5_string("{a: NaN}").is_ok());
+    }
+
+    #[test]
+    fn json5_hex_numbers_have_no_sign_after_0x() {
+        assert_eq!(
+            relaxed("[0x+5, 0x-5]"),
+            [
+                "[",
+                "error: invalid value `0x+5` at line 1 column 2",
+                "error: invalid value `0x-5` at line 1 column 8",
+                "]"
+            ]
+        );
+    }
+
+    #[test]
+    fn json5_reports_the_first_error() {
+        assert!(parse_json5_string("{a: 1, // comment\n}").is_ok());
+        let error = parse_json5_string("{\n  a: 1,\n  b: ?\n}").err().unwrap();
+        assert_eq!(
+            (error.message.as_str(), error.line, error.column),
+            ("unexpected `?`", 3, 6)
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use std::fmt;
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::{fs, io};
+
+use crate::args::{Args, Format};
+use crate::decompress::{decompressing_reader, is_compressed};
+use crate::json_item::JsonItem;
+use crate::lazy_json::{LazyDocument, LAZY_THRESHOLD_BYTES};
+use crate::{lenient_json, parse_cbor, parse_json, parse_msgpack, parse_toml, parse_yaml};
+
+/// Exit codes that scripts can rely on
+pub const EXIT_READ_ERROR: i32 = 2;
+pub const EXIT_PARSE_ERROR: i32 = 3;
+
+const MAX_SNIPPET_WIDTH: usize = 80;
+
+/// How much of a lazily loaded file to look at for the magic number of a compression format
+const SNIFF_BYTES: usize = 8;
+
+pub enum LoadError {
+    Read {
+        filename: String,
+        error: io::Error,
+    },
+    Parse {
+        filename: String,
+        message: String,
+        line: usize,
+        column: usize,
+        line_text: String,
+        recoverable: bool,
+    },
+}
+
+impl LoadError {
+    fn parse(filename: &str, error: serde_json::Error, text: &str) -> LoadError {
+        let message = error.to_string();
+        let message = match message.rsplit_once(" at line ") {
+            Some((message, _)) => message.to_string(),
+            None => message,
+        };
+        LoadError::syntax(filename, message, error.line(), error.column(), text)
+    }
+
+    fn yaml(filename: &str, error: serde_yaml::Error, text: &str) -> LoadError {
+        let message = error.to_string();
+        let message = match message.split_once(" at line ") {
+            Some((message, _)) => message.to_string(),
+            None => message,
+        };
+        let (line, column) = match error.location() {
+            Some(location) => (location.line(), location.column()),
+            None => (0, 0),
+        };
+        LoadError::unrecoverable(LoadError::syntax(filename, message, line, column, text))
+    }
+
+    fn toml(filename: &str, error: toml::de::Error, text: &str) -> LoadError {
+        let (line, column) = match error.span() {
+            Some(span) => {
+                let before = &text[..span.start];
+                let line_start = before.rfind('\n').map(|index| index + 1).unwrap_or(0);
+                (
+                    before.matches('\n').count() + 1,
+                    span.start - line_start + 1,
+                )
+            }
+            None => (0, 0),
+        };
+        let message = error.message().trim_end().replace('\n', ", ");
+        LoadError::unrecoverable(LoadError::syntax(filename, message, line, column, text))
+    }
+
+    /// Errors in binary formats have no line to point at
+    fn binary(filename: &str, message: String) -> LoadError {
+        LoadError::unrecoverable(LoadError::syntax(filename, message, 0, 0, ""))
+    }
+
+    /// Parse errors in formats without a lenient parser
+    fn unrecoverable(mut error: LoadError) -> LoadError {
+        if let LoadError::Parse { recoverable, .. } = &mut error {
+            *recoverable = false;
+        }
+        error
+    }
+
+    fn syntax(
+        filename: &str,
+        message: String,
+        line: usize,
+        column: usize,
+        text: &str,
+    ) -> LoadError {
+        let line_text = match line {
+            0 => "",
+            line => text.lines().nth(line - 1).unwrap_or(""),
+        };
+        LoadError::Parse {
+            filename: filename.to_string(),
+            message,
+            line,
+            column,
+            line_text: line_text.to_string(),
+            recoverable: true,
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LoadError::Read { .. } => EXIT_READ_ERROR,
+            LoadError::Parse { .. } => EXIT_PARSE_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Read { filename, error } => {
+                write!(f, "error: could not read {}: {}", filename, error)
+            }
+            LoadError::Parse {
+                filename,
+                message,
+                line: 0,
+                ..
+            } => write!(f, "error: {} in {}", message, filename),
+            LoadError::Parse {
+                filename,
+                message,
+                line,
+                column,
+                line_text,
+                recoverable,
+            } => {
+                writeln!(
+                    f,
+                    "error: {} at line {} column {} in {}",
+                    message, line, column, filename
+                )?;
+                let (snippet, caret_offset) = snippet(line_text, *column);
+                let gutter = line.to_string();
+                writeln!(f, "{} | {}", gutter, snippet)?;
+                write!(
+                    f,
+                    "{} | {}^",
+                    " ".repeat(gutter.len()),
+                    " ".repeat(caret_offset)
+                )?;
+                if *recoverable {
+                    write!(
+                        f,
+                        "\nRun with --lenient to browse the parts that can be parsed."
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Cut a window out of a (possibly very long) line so that the error column stays visible.
+/// Returns the snippet and the caret position within it, both counted in characters.
+fn snippet(line_text: &str, column: usize) -> (String, usize) {
+    let chars: Vec<char> = line_text.chars().collect();
+    // serde_json columns are 1-based byte offsets
+    let byte_offset = column.saturating_sub(1).min(line_text.len());
+    let error_index = line_text
+        .char_indices()
+        .take_while(|(index, _)| *index < byte_offset)
+        .count();
+    let start = error_index.saturating_sub(MAX_SNIPPET_WIDTH / 2);
+    let end = (start + MAX_SNIPPET_WIDTH).min(chars.len());
+    let mut snippet: String = chars[start..end].iter().collect();
+    let mut caret_offset = error_index - start;
+    if start > 0 {
+        snippet = format!("…{}", snippet);
+        caret_offset += 1;
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    (snippet, caret_offset)
+}
+
+/// How far loading has got, shared between the loading thread and the UI
+#[derive(Default)]
+pub struct Progress {
+    stage: Mutex<&'static str>,
+    done: AtomicU64,
+    total: AtomicU64,
+}
+
+impl Progress {
+    /// Start the next stage. A `total` of 0 means the size isn't known in advance.
+    pub fn start(&self, stage: &'static str, total: u64) {
+        *self.stage.lock().unwrap() = stage;
+        self.done.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, bytes: usize) {
+        self.done.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// The current stage and the number of bytes done out of the total
+    pub fn get(&self) -> (&'static str, u64, u64) {
+        (
+            *self.stage.lock().unwrap(),
+            self.done.load(Ordering::Relaxed),
+            self.total.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Counts the bytes that pass through it
+struct ProgressReader<'a, R> {
+    inner: R,
+    progress: &'a Progress,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let num_read = self.inner.read(buf)?;
+        self.progress.advance(num_read);
+        Ok(num_read)
+    }
+}
+
+/// Read the whole input, decompressing it on the fly if necessary
+fn read_input(args: &Args, progress: &Progress) -> Result<(Vec<u8>, String), LoadError> {
+    let (reader, filename, size): (Box<dyn Read>, String, u64) = if args.reads_stdin() {
+        (Box::new(io::stdin()), "<stdin>".to_string(), 0)
+    } else {
+        let filename = args.input_file.clone();
+        match fs::File::open(&filename) {
+            Ok(file) => {
+                let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+                (Box::new(file), filename, size)
+            }
+            Err(error) => return Err(LoadError::Read { filename, error }),
+        }
+    };
+    progress.start("Reading", size);
+    // Count the bytes taken from the file, before decompression, so they match its size
+    let reader = ProgressReader {
+        inner: reader,
+        progress,
+    };
+    let mut bytes = Vec::new();
+    match decompressing_reader(reader).and_then(|mut reader| reader.read_to_end(&mut bytes)) {
+        Ok(_) => Ok((bytes, filename)),
+        Err(error) => Err(LoadError::Read { filename, error }),
+    }
+}
+
+pub struct Document {
+    pub items: Vec<JsonItem>,
+    /// Title to show for the document
+    pub filename: String,
+    /// Set when the file is parsed on demand
+    pub lazy_document: Option<LazyDocument>,
+}
+
+/// Memory-map large plain JSON files, or any JSON file with `--lazy`. Returns `None` when
+/// the input has to be read in full, e.g. because it's compressed or in another format.
+fn open_lazy(args: &Args, progress: &Progress) -> Option<LazyDocument> {
+    if args.reads_stdin() || args.lenient {
+        return None;
+    }
+    if !matches!(args.format_hint(), None | Some(Format::Json)) {
+        return None;
+    }
+    let file = fs::File::open(&args.input_file).ok()?;
+    let size = file.metadata().ok()?.len();
+    if !args.lazy && size < LAZY_THRESHOLD_BYTES {
+        return None;
+    }
+    progress.start("Indexing", size);
+    let lazy_document = LazyDocument::open(&file, progress).ok()?;
+    // More values after the first one are JSON Lines, or an error to report
+    if is_compressed(lazy_document.prefix(SNIFF_BYTES)) || lazy_document.has_trailing_data() {
+        return None;
+    }
+    Some(lazy_document)
+}
+
+/// Read the input named on the command line and parse it into items. This can take a
+/// while for large files, so it reports how far it has got to `progress`.
+pub fn load(args: &Args, progress: &Progress) -> Result<Document, LoadError> {
+    if let Some(lazy_document) = open_lazy(args, progress) {
+        return Ok(Document {
+            items: lazy_document.root_items(),
+            filename: args.input_file.clone(),
+            lazy_document: Some(lazy_document),
+        });
+    }
+
+    let (bytes, filename) = read_input(args, progress)?;
+    let items = parse_input(args, bytes, &filename, progress)?;
+    Ok(Document {
+        items,
+        filename,
+        lazy_document: None,
+    })
+}
+
+fn parse_input(
+    args: &Args,
+    bytes: Vec<u8>,
+    filename: &str,
+    progress: &Progress,
+) -> Result<Vec<JsonItem>, LoadError> {
+    progress.start("Parsing", 0);
+    // Binary formats don't have to be UTF-8; text formats fail when they need the text
+    let text = std::str::from_utf8(&bytes);
+    let text = || {
+        text.map_err(|error| LoadError::Read {
+            filename: filename.to_string(),
+            error: io::Error::new(io::ErrorKind::InvalidData, error),
+        })
+    };
+    let format = match args.format_hint() {
+        Some(format) => format,
+        None if parse_json::looks_like_json_lines(text()?) => Format::JsonLines,
+        None => Format::Json,
+    };
+    let json_values = match format {
+        Format::MessagePack => parse_msgpack::parse_msgpack_bytes(&bytes)
+            .map_err(|error| LoadError::binary(filename, error.to_string()))?,
+        Format::Cbor => parse_cbor::parse_cbor_bytes(&bytes)
+            .map_err(|error| LoadError::binary(filename, error))?,
+        Format::Json if args.lenient => lenient_json::parse_lenient_json_string(text()?, false),
+        Format::Json => {
+            let json_text = text()?;
+            parse_json::parse_json_string(json_text)
+                .map_err(|error| LoadError::parse(filename, error, json_text))?
+        }
+        Format::JsonLines => parse_json::parse_json_lines_string(text()?),
+        Format::Json5 if args.lenient => lenient_json::parse_lenient_json_string(text()?, true),
+        Format::Json5 => {
+            let json_text = text()?;
+            lenient_json::parse_json5_string(json_text).map_err(|error| {
+                LoadError::syntax(filename, error.message, error.line, error.column, json_text)
+            })?
+        }
+        Format::Yaml => {
+            let json_text = text()?;
+            parse_yaml::parse_yaml_string(json_text)
+                .map_err(|error| LoadError::yaml(filename, error, json_text))?
+        }
+        Format::Toml => {
+            let json_text = text()?;
+            parse_toml::parse_toml_string(json_text)
+                .map_err(|error| LoadError::toml(filename, error, json_text))?
+        }
+    };
+    Ok(json_values)
+}
@@ -1,6 +1,9 @@
 use std::error::Error;
-use std::io::Stdout;
+use std::io::{IsTerminal, Stdout};
 use std::process::exit;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 use std::{fs, io};
 
 use crossterm::{
@@ -11,27 +14,79 @@ use crossterm::{
 use ratatui::{backend::CrosstermBackend, Terminal};
 
 use crate::app_state::AppState;
+use crate::args::Args;
+use crate::load::Progress;
 
 mod ui;
 
 mod app_state;
+mod args;
+mod background;
+mod decompress;
+mod fenwick_tree;
+mod fuzzy_finder;
+mod jq_filter;
 mod json_item;
+mod json_path;
+mod lazy_json;
+mod lenient_json;
+mod load;
+mod parse_cbor;
 mod parse_json;
+mod parse_msgpack;
+mod parse_toml;
+mod parse_yaml;
+mod predicate;
+mod results_panel;
 mod search;
+mod search_history;
 mod theme;
+mod tree;
+
+/// Files that load faster than this open without showing the progress screen
+const PROGRESS_DELAY: Duration = Duration::from_millis(200);
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let mut args = std::env::args();
-    if args.len() != 2 {
-        println!("Usage: `jex [INPUT_FILE]`");
-        exit(1);
+    let args = Args::parse();
+    if args.reads_stdin() && io::stdin().is_terminal() {
+        args::usage();
+    }
+
+    let title = match args.reads_stdin() {
+        true => "<stdin>".to_string(),
+        false => args.input_file.clone(),
+    };
+    let progress = Arc::new(Progress::default());
+    let loader = {
+        let progress = progress.clone();
+        thread::spawn(move || load::load(&args, &progress))
+    };
+
+    let mut terminal = None;
+    if !wait_for(&loader, PROGRESS_DELAY) {
+        let terminal = terminal.insert(create_terminal());
+        let loaded = ui::show_progress(terminal, &loader, &progress, &title);
+        if !matches!(loaded, Ok(true)) {
+            destroy_terminal(terminal);
+            if let Err(err) = loaded {
+                println!("{:?}", err)
+            }
+            return Ok(());
+        }
     }
-    let input_file: String = args.nth(1).unwrap();
-    let json_text = fs::read_to_string(&input_file).expect("Could not read from file");
-    let json_values = parse_json::parse_json_string(&json_text).expect("Could not parse json.");
+    let document = match loader.join().expect("Loading thread panicked") {
+        Ok(document) => document,
+        Err(err) => {
+            if let Some(terminal) = &mut terminal {
+                destroy_terminal(terminal);
+            }
+            eprintln!("{}", err);
+            exit(err.exit_code());
+        }
+    };
 
-    let mut app_state = AppState::new(json_values, input_file);
-    let mut terminal: Terminal<CrosstermBackend<Stdout>> = create_terminal();
+    let mut app_state = AppState::new(document);
+    let mut terminal: Terminal<CrosstermBackend<Stdout>> = terminal.unwrap_or_else(create_terminal);
 
     let res = ui::run_app(&mut terminal, &mut app_state);
 
@@ -44,7 +99,21 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Returns whether the thread finished within `timeout`
+fn wait_for<T>(thread: &JoinHandle<T>, timeout: Duration) -> bool {
+    let start = Instant::now();
+    while !thread.is_finished() && start.elapsed() < timeout {
+        thread::sleep(Duration::from_millis(10));
+    }
+    thread.is_finished()
+}
+
 fn create_terminal() -> Terminal<CrosstermBackend<Stdout>> {
+    // When the document was piped in, stdin is at EOF. Crossterm reads keys and switches
+    // to raw mode through /dev/tty whenever stdin is not a terminal, so make sure it exists.
+    if !io::stdin().is_terminal() {
+        fs::File::open("/dev/tty").expect("Unable to open /dev/tty for keyboard input");
+    }
     enable_raw_mode().expect("Unable to enable raw mode");
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture).expect("Unable to set up stdout");
@@ -0,0 +1,97 @@
+use ciborium::de::Error;
+use ciborium::Value;
+
+use crate::json_item::{JsonItem, JsonValueType};
+use crate::parse_json::ItemBuilder;
+
+fn parse_cbor(root_value: &Value, builder: &mut ItemBuilder, title: Option<String>) {
+    match root_value {
+        Value::Map(map) => {
+            builder.open_object(title);
+            for (key, value) in map {
+                parse_cbor(value, builder, Some(key_to_string(key)));
+            }
+            builder.close();
+        }
+        Value::Array(arr) => {
+            builder.open_array(title);
+            for value in arr {
+                parse_cbor(value, builder, None);
+            }
+            builder.close();
+        }
+        Value::Integer(i) => {
+            let i = i128::from(*i);
+            let value = match (i64::try_from(i), u64::try_from(i)) {
+                (Ok(i), _) => JsonValueType::Number(i.into()),
+                (_, Ok(u)) => JsonValueType::Number(u.into()),
+                // Negative integers below i64::MIN
+                _ => JsonValueType::String(i.to_string()),
+            };
+            builder.value(title, value)
+        }
+        Value::Float(f) => builder.float(title, *f),
+        Value::Bool(b) => builder.value(title, JsonValueType::Bool(*b)),
+        Value::Text(s) => builder.value(title, JsonValueType::String(s.clone())),
+        Value::Bytes(bytes) => builder.value(title, JsonValueType::Bytes(bytes.clone())),
+        Value::Null => builder.value(title, JsonValueType::Null),
+        Value::Tag(tag, value) => {
+            builder.tag_next(tag_name(*tag));
+            parse_cbor(value, builder, title)
+        }
+        _ => builder.value(title, JsonValueType::Null),
+    }
+}
+
+/// Name the common tags from RFC 8949
+fn tag_name(tag: u64) -> String {
+    match tag {
+        0 => "tag 0: date/time".to_string(),
+        1 => "tag 1: epoch time".to_string(),
+        2 => "tag 2: bignum".to_string(),
+        3 => "tag 3: negative bignum".to_string(),
+        4 => "tag 4: decimal fraction".to_string(),
+        32 => "tag 32: URI".to_string(),
+        37 => "tag 37: UUID".to_string(),
+        _ => format!("tag {}", tag),
+    }
+}
+
+/// Scalar keys that aren't text are shown as their value, e.g. `1` or `true`, and other
+/// keys in debug notation
+fn key_to_string(key: &Value) -> String {
+    match key {
+        Value::Text(s) => s.clone(),
+        Value::Integer(i) => i128::from(*i).to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Null => "null".to_string(),
+        _ => format!("{:?}", key),
+    }
+}
+
+/// Parse CBOR. A sequence of several items (RFC 8742) is shown as the elements of a
+/// virtual top-level array.
+pub fn parse_cbor_bytes(bytes: &[u8]) -> Result<Vec<JsonItem>, String> {
+    let mut rest = bytes;
+    let mut values: Vec<Value> = Vec::new();
+    while !rest.is_empty() {
+        let offset = bytes.len() - rest.len();
+        let value = ciborium::from_reader(&mut rest).map_err(|error| match error {
+            Error::Io(_) => "unexpected end of input".to_string(),
+            Error::Syntax(index) => format!("syntax error at byte {}", offset + index),
+            Error::Semantic(Some(index), message) => {
+                format!("{} at byte {}", message, offset + index)
+            }
+            Error::Semantic(None, message) => message,
+            Error::RecursionLimitExceeded => "nesting too deep".to_string(),
+        })?;
+        values.push(value);
+    }
+
+    let mut builder = ItemBuilder::new();
+    builder.values(&values, "value", |value, builder| {
+        parse_cbor(value, builder, None)
+    });
+    Ok(builder.finish())
+}
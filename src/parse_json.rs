@@ -1,121 +1,370 @@
-use crate::json_item::{JsonItem, JsonValueType};
+use std::ops::Range;
+
+use crate::json_item::{JsonItem, JsonValueType, UnloadedMembers};
 use serde_json::{Result, Value};
 
-fn parse_json(
-    root_value: &Value,
-    output: &mut Vec<JsonItem>,
-    title: Option<String>,
-    indent: usize,
-    breadcrumbs: String,
-) {
+struct OpenContainer {
+    index: usize,
+    value_type: JsonValueType,
+    len: usize,
+    last_child: Option<usize>,
+}
+
+/// Builds the items in document order. Parsers open and close containers and push
+/// values; indents, lengths, line numbers and the links between parents, children,
+/// siblings and matching brackets are filled in here.
+pub struct ItemBuilder {
+    items: Vec<JsonItem>,
+    stack: Vec<OpenContainer>,
+    next_label: Option<String>,
+    next_tag: Option<String>,
+    base_indent: usize,
+    /// Line number of the first item, when building children that will be inserted
+    /// into an existing document
+    offset: usize,
+}
+
+impl ItemBuilder {
+    pub fn new() -> ItemBuilder {
+        ItemBuilder {
+            items: Vec::new(),
+            stack: Vec::new(),
+            next_label: None,
+            next_tag: None,
+            base_indent: 0,
+            offset: 0,
+        }
+    }
+
+    /// Start a builder for the children of an already existing container, used when
+    /// containers are loaded lazily. The children are meant to be inserted right after
+    /// `parent`; linking `parent` to its first child is left to the caller.
+    pub fn for_children_of(parent: &JsonItem) -> ItemBuilder {
+        ItemBuilder::for_members_of(parent, 0, parent.line_number + 1)
+    }
+
+    /// Like `for_children_of`, for members that start at position `first_member` in
+    /// `parent` and are inserted at `line_number`. Linking them to the members around
+    /// them is left to the caller.
+    pub fn for_members_of(
+        parent: &JsonItem,
+        first_member: usize,
+        line_number: usize,
+    ) -> ItemBuilder {
+        ItemBuilder {
+            items: Vec::new(),
+            stack: vec![OpenContainer {
+                index: parent.line_number,
+                value_type: parent.value.clone(),
+                len: first_member,
+                last_child: None,
+            }],
+            next_label: None,
+            next_tag: None,
+            base_indent: parent.indent,
+            offset: line_number,
+        }
+    }
+
+    /// Use `label` instead of the key or index in the breadcrumbs of the next item
+    pub fn label_next(&mut self, label: String) {
+        self.next_label = Some(label);
+    }
+
+    /// Annotate the next item, e.g. with a CBOR tag
+    pub fn tag_next(&mut self, tag: String) {
+        self.next_tag = Some(tag);
+    }
+
+    pub fn open_object(&mut self, name: Option<String>) {
+        self.open(name, JsonValueType::Object);
+    }
+
+    pub fn open_array(&mut self, name: Option<String>) {
+        self.open(name, JsonValueType::Array);
+    }
+
+    fn open(&mut self, name: Option<String>, value_type: JsonValueType) {
+        let index = self.push_child(name, value_type.clone());
+        self.stack.push(OpenContainer {
+            index,
+            value_type,
+            len: 0,
+            last_child: None,
+        });
+    }
+
+    /// A collapsed container whose children are only read from `span` when it's expanded
+    pub fn unloaded(
+        &mut self,
+        name: Option<String>,
+        value_type: JsonValueType,
+        span: Range<usize>,
+        len: usize,
+    ) {
+        self.open(name, value_type);
+        self.stack.last_mut().unwrap().len = len;
+        self.items.last_mut().unwrap().unloaded_span = Some(span);
+        self.close();
+    }
+
+    /// Close the innermost open container
+    pub fn close(&mut self) {
+        if let Some(container) = self.stack.pop() {
+            let end_type = match container.value_type {
+                JsonValueType::Array => JsonValueType::ArrayEnd,
+                _ => JsonValueType::ObjectEnd,
+            };
+            let mut item = JsonItem::new(None, self.indent(), end_type, 0);
+            item.parent = self.stack.last().map(|parent| parent.index);
+            item.bracket = Some(container.index);
+            let end_index = self.push(item);
+            let opener = self.item_mut(container.index).unwrap();
+            opener.len = container.len;
+            opener.bracket = Some(end_index);
+        }
+    }
+
+    pub fn value(&mut self, name: Option<String>, value: JsonValueType) {
+        self.push_child(name, value);
+    }
+
+    pub fn float(&mut self, name: Option<String>, f: f64) {
+        self.value(name, float_value(f));
+    }
+
+    /// The values of a file that can hold several, like a YAML stream. One value is the
+    /// root, none is `null`, and several are the elements of a virtual top-level array,
+    /// labelled e.g. `[document 2]`.
+    pub fn values<T>(&mut self, values: &[T], kind: &str, mut parse: impl FnMut(&T, &mut Self)) {
+        match values {
+            [] => self.value(None, JsonValueType::Null),
+            [value] => parse(value, self),
+            _ => {
+                self.open_array(None);
+                for (index, value) in values.iter().enumerate() {
+                    self.label_next(format!("[{} {}]", kind, index + 1));
+                    parse(value, self);
+                }
+                self.close();
+            }
+        }
+    }
+
+    /// Insert an error marker. It is not counted as a child of the enclosing container.
+    pub fn error(&mut self, message: String) {
+        self.next_label = None;
+        self.annotation(JsonValueType::Error(message));
+    }
+
+    /// Insert a source comment. Like errors, comments are not counted as children.
+    pub fn comment(&mut self, text: String) {
+        self.annotation(JsonValueType::Comment(text));
+    }
+
+    /// Stand in for members of the innermost container that are only read later. The
+    /// members after them keep their positions.
+    pub fn unloaded_members(&mut self, unloaded: UnloadedMembers) {
+        let num_members = unloaded.members.len();
+        self.annotation(JsonValueType::Unloaded(unloaded));
+        if let Some(container) = self.stack.last_mut() {
+            container.len += num_members;
+        }
+    }
+
+    fn annotation(&mut self, value: JsonValueType) {
+        let item = JsonItem::new(None, self.indent(), value, 0);
+        self.link_child(item);
+    }
+
+    /// Push a member of the innermost container, counting it in its length
+    fn push_child(&mut self, name: Option<String>, value: JsonValueType) -> usize {
+        let mut item = JsonItem::new(name, self.indent(), value, 0);
+        item.tag = self.next_tag.take();
+        item.label = self.next_label.take();
+        if let Some(container) = self.stack.last_mut() {
+            item.index_in_parent = container.len;
+            container.len += 1;
+        }
+        self.link_child(item)
+    }
+
+    /// Push an item as the last child of the innermost container
+    fn link_child(&mut self, mut item: JsonItem) -> usize {
+        let index = self.offset + self.items.len();
+        let previous = match self.stack.last_mut() {
+            Some(container) => {
+                item.parent = Some(container.index);
+                container.last_child.replace(index)
+            }
+            None => None,
+        };
+        match previous {
+            Some(previous) => self.item_mut(previous).unwrap().next_sibling = Some(index),
+            None => {
+                let parent = item.parent;
+                if let Some(parent) = parent.and_then(|parent| self.item_mut(parent)) {
+                    parent.first_child = Some(index);
+                }
+            }
+        }
+        self.push(item)
+    }
+
+    fn push(&mut self, mut item: JsonItem) -> usize {
+        let index = self.offset + self.items.len();
+        item.line_number = index;
+        self.items.push(item);
+        index
+    }
+
+    /// The item with the given line number, unless it's outside of what is being built
+    fn item_mut(&mut self, index: usize) -> Option<&mut JsonItem> {
+        index
+            .checked_sub(self.offset)
+            .and_then(|index| self.items.get_mut(index))
+    }
+
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    fn indent(&self) -> usize {
+        self.base_indent + self.stack.len()
+    }
+
+    pub fn is_in_array(&self) -> bool {
+        matches!(
+            self.stack.last(),
+            Some(OpenContainer {
+                value_type: JsonValueType::Array,
+                ..
+            })
+        )
+    }
+
+    /// Close any containers left open and return the items
+    pub fn finish(mut self) -> Vec<JsonItem> {
+        while !self.stack.is_empty() {
+            self.close();
+        }
+        self.items
+    }
+
+    /// Return the items built with `for_children_of`
+    pub fn finish_children(mut self) -> Vec<JsonItem> {
+        while self.stack.len() > 1 {
+            self.close();
+        }
+        self.items
+    }
+}
+
+/// A number, or for inf and nan, which have no JSON equivalent, a string
+pub fn float_value(f: f64) -> JsonValueType {
+    match serde_json::Number::from_f64(f) {
+        Some(n) => JsonValueType::Number(n),
+        None => JsonValueType::String(f.to_string()),
+    }
+}
+
+pub fn parse_json(root_value: &Value, builder: &mut ItemBuilder, title: Option<String>) {
     match root_value {
         Value::Object(map) => {
-            output.push(JsonItem::new(
-                title,
-                indent,
-                JsonValueType::Object,
-                breadcrumbs.clone(),
-                map.len(),
-            ));
+            builder.open_object(title);
             for (key, value) in map {
-                parse_json(
-                    value,
-                    output,
-                    Some(key.to_string()),
-                    indent + 1,
-                    make_breadcrumbs(&breadcrumbs, key, JsonValueType::Object),
-                );
+                parse_json(value, builder, Some(key.to_string()));
             }
-            output.push(JsonItem::new(
-                None,
-                indent,
-                JsonValueType::ObjectEnd,
-                breadcrumbs.clone(),
-                0,
-            ));
+            builder.close();
         }
         Value::Array(arr) => {
-            output.push(JsonItem::new(
-                title.clone(),
-                indent,
-                JsonValueType::Array,
-                breadcrumbs.clone(),
-                arr.len(),
-            ));
-            for (index, value) in arr.iter().enumerate() {
-                parse_json(
-                    value,
-                    output,
-                    None,
-                    indent + 1,
-                    make_breadcrumbs(&breadcrumbs, &index.to_string(), JsonValueType::Array),
-                );
+            builder.open_array(title);
+            for value in arr {
+                parse_json(value, builder, None);
             }
-            output.push(JsonItem::new(
-                None,
-                indent,
-                JsonValueType::ArrayEnd,
-                breadcrumbs.clone(),
-                0,
-            ));
-        }
-        Value::Number(n) => {
-            output.push(JsonItem::new(
-                title,
-                indent,
-                JsonValueType::Number(n.clone()),
-                breadcrumbs.clone(),
-                0,
-            ));
+            builder.close();
         }
-        Value::Bool(b) => {
-            output.push(JsonItem::new(
-                title,
-                indent,
-                JsonValueType::Bool(*b),
-                breadcrumbs.clone(),
-                0,
-            ));
-        }
-        Value::String(s) => {
-            output.push(JsonItem::new(
-                title,
-                indent,
-                JsonValueType::String(s.clone()),
-                breadcrumbs.clone(),
-                0,
-            ));
+        Value::Number(n) => builder.value(title, JsonValueType::Number(n.clone())),
+        Value::Bool(b) => builder.value(title, JsonValueType::Bool(*b)),
+        Value::String(s) => builder.value(title, JsonValueType::String(s.clone())),
+        Value::Null => builder.value(title, JsonValueType::Null),
+    }
+}
+
+pub fn parse_json_string(json_string: &str) -> Result<Vec<JsonItem>> {
+    let root_value: Value = serde_json::from_str(json_string)?;
+
+    let mut builder = ItemBuilder::new();
+    parse_json(&root_value, &mut builder, None);
+    Ok(builder.finish())
+}
+
+/// Parse newline-delimited JSON. Every non-empty line becomes an element of a virtual
+/// top-level array, and lines that fail to parse become error items.
+pub fn parse_json_lines_string(json_string: &str) -> Vec<JsonItem> {
+    let mut builder = ItemBuilder::new();
+    builder.open_array(None);
+    for (index, line) in json_string.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
         }
-        Value::Null => {
-            output.push(JsonItem::new(
-                title,
-                indent,
-                JsonValueType::Null,
-                breadcrumbs.clone(),
-                0,
-            ));
+        match serde_json::from_str::<Value>(line) {
+            Ok(value) => {
+                builder.label_next(format!("[line {}]", index + 1));
+                parse_json(&value, &mut builder, None);
+            }
+            // Not an element, so the indexes of the lines after it still match the array
+            Err(err) => builder.error(error_message(&err, index + 1)),
         }
     }
+    builder.finish()
 }
 
-fn make_breadcrumbs(root: &str, new: &str, value_type: JsonValueType) -> String {
-    match root {
-        "" => new.to_string(),
-        _ => match value_type {
-            JsonValueType::Array => format!("{} ▶ [{}]", root, new),
-            JsonValueType::Object => format!("{} ▶ {}", root, new),
-            _ => format!("{} ▶ {}", root, new),
-        },
+/// Guess whether the text is JSON Lines: the first line must be a complete
+/// document on its own, followed by more content.
+pub fn looks_like_json_lines(json_string: &str) -> bool {
+    let mut lines = json_string.lines().filter(|line| !line.trim().is_empty());
+    match (lines.next(), lines.next()) {
+        (Some(first), Some(_)) => serde_json::from_str::<Value>(first).is_ok(),
+        _ => false,
     }
 }
 
-pub fn parse_json_string(json_string: &str) -> Result<Vec<JsonItem>> {
-    let root_value: Value = serde_json::from_str(json_string)?;
+/// Describe an error on one line of JSON Lines, where serde_json always reports line 1
+fn error_message(err: &serde_json::Error, line_number: usize) -> String {
+    let message = err.to_string();
+    match message.rsplit_once(" at line ") {
+        Some((message, _)) => format!(
+            "{} at line {} column {}",
+            message,
+            line_number,
+            err.column()
+        ),
+        None => format!("{} at line {}", message, line_number),
+    }
+}
 
-    let mut json_vec = Vec::new();
-    parse_json(&root_value, &mut json_vec, None, 0, "".to_string());
-    for (index, item) in json_vec.iter_mut().enumerate() {
-        item.line_number = index;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_lines_errors_keep_their_line_and_are_not_elements() {
+        let items = parse_json_lines_string("{\"a\": 1}\n\n{oops\n[2]\n");
+        let errors: Vec<&str> = items
+            .iter()
+            .filter_map(|item| match &item.value {
+                JsonValueType::Error(message) => Some(message.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("at line 3 column"), "{}", errors[0]);
+
+        let line_4 = items
+            .iter()
+            .find(|item| item.label.as_deref() == Some("[line 4]"))
+            .unwrap();
+        assert_eq!(line_4.index_in_parent, 1);
     }
-    Ok(json_vec)
 }
@@ -0,0 +1,67 @@
+use rmpv::Value;
+
+use crate::json_item::{JsonItem, JsonValueType};
+use crate::parse_json::ItemBuilder;
+
+fn parse_msgpack(root_value: &Value, builder: &mut ItemBuilder, title: Option<String>) {
+    match root_value {
+        Value::Map(map) => {
+            builder.open_object(title);
+            for (key, value) in map {
+                parse_msgpack(value, builder, Some(key_to_string(key)));
+            }
+            builder.close();
+        }
+        Value::Array(arr) => {
+            builder.open_array(title);
+            for value in arr {
+                parse_msgpack(value, builder, None);
+            }
+            builder.close();
+        }
+        Value::Integer(i) => {
+            let number = match i.as_i64() {
+                Some(i) => serde_json::Number::from(i),
+                None => serde_json::Number::from(i.as_u64().unwrap_or_default()),
+            };
+            builder.value(title, JsonValueType::Number(number))
+        }
+        Value::F32(f) => builder.float(title, *f as f64),
+        Value::F64(f) => builder.float(title, *f),
+        Value::Boolean(b) => builder.value(title, JsonValueType::Bool(*b)),
+        Value::String(s) => match s.as_str() {
+            Some(s) => builder.value(title, JsonValueType::String(s.to_string())),
+            None => builder.value(title, JsonValueType::Bytes(s.as_bytes().to_vec())),
+        },
+        Value::Binary(bytes) => builder.value(title, JsonValueType::Bytes(bytes.clone())),
+        Value::Ext(ext_type, bytes) => {
+            builder.tag_next(format!("ext {}", ext_type));
+            builder.value(title, JsonValueType::Bytes(bytes.clone()))
+        }
+        Value::Nil => builder.value(title, JsonValueType::Null),
+    }
+}
+
+/// Keys that aren't UTF-8 strings are shown the way rmpv displays them, e.g. `1` or `[1, 2]`
+fn key_to_string(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.as_str().map(|s| s.to_string()).unwrap_or(key.to_string()),
+        _ => key.to_string(),
+    }
+}
+
+/// Parse MessagePack. Several concatenated values are shown as the elements of a virtual
+/// top-level array.
+pub fn parse_msgpack_bytes(bytes: &[u8]) -> Result<Vec<JsonItem>, rmpv::decode::Error> {
+    let mut rest = bytes;
+    let mut values = Vec::new();
+    while !rest.is_empty() {
+        values.push(rmpv::decode::read_value(&mut rest)?);
+    }
+
+    let mut builder = ItemBuilder::new();
+    builder.values(&values, "value", |value, builder| {
+        parse_msgpack(value, builder, None)
+    });
+    Ok(builder.finish())
+}
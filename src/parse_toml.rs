@@ -0,0 +1,38 @@
+use toml::{Table, Value};
+
+use crate::json_item::{JsonItem, JsonValueType};
+use crate::parse_json::ItemBuilder;
+
+fn parse_toml(root_value: &Value, builder: &mut ItemBuilder, title: Option<String>) {
+    match root_value {
+        Value::Table(table) => parse_table(table, builder, title),
+        Value::Array(arr) => {
+            builder.open_array(title);
+            for value in arr {
+                parse_toml(value, builder, None);
+            }
+            builder.close();
+        }
+        Value::Integer(i) => builder.value(title, JsonValueType::Number((*i).into())),
+        Value::Float(f) => builder.float(title, *f),
+        Value::Boolean(b) => builder.value(title, JsonValueType::Bool(*b)),
+        Value::String(s) => builder.value(title, JsonValueType::String(s.clone())),
+        Value::Datetime(d) => builder.value(title, JsonValueType::DateTime(d.to_string())),
+    }
+}
+
+fn parse_table(table: &Table, builder: &mut ItemBuilder, title: Option<String>) {
+    builder.open_object(title);
+    for (key, value) in table {
+        parse_toml(value, builder, Some(key.to_string()));
+    }
+    builder.close();
+}
+
+pub fn parse_toml_string(toml_string: &str) -> Result<Vec<JsonItem>, toml::de::Error> {
+    let root_table: Table = toml::from_str(toml_string)?;
+
+    let mut builder = ItemBuilder::new();
+    parse_table(&root_table, &mut builder, None);
+    Ok(builder.finish())
+}
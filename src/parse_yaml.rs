@@ -0,0 +1,70 @@
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::json_item::{JsonItem, JsonValueType};
+use crate::parse_json::{float_value, ItemBuilder};
+
+fn parse_yaml(root_value: &Value, builder: &mut ItemBuilder, title: Option<String>) {
+    match root_value {
+        Value::Mapping(map) => {
+            builder.open_object(title);
+            for (key, value) in map {
+                parse_yaml(value, builder, Some(key_to_string(key)));
+            }
+            builder.close();
+        }
+        Value::Sequence(seq) => {
+            builder.open_array(title);
+            for value in seq {
+                parse_yaml(value, builder, None);
+            }
+            builder.close();
+        }
+        Value::Number(n) => builder.value(title, number_value(n)),
+        Value::Bool(b) => builder.value(title, JsonValueType::Bool(*b)),
+        Value::String(s) => builder.value(title, JsonValueType::String(s.clone())),
+        Value::Null => builder.value(title, JsonValueType::Null),
+        Value::Tagged(tagged) => parse_yaml(&tagged.value, builder, title),
+    }
+}
+
+/// YAML allows any value as a mapping key; non-string keys are shown as they'd be written
+fn key_to_string(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.clone(),
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        _ => serde_yaml::to_string(key)
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn number_value(n: &serde_yaml::Number) -> JsonValueType {
+    if let Some(i) = n.as_i64() {
+        JsonValueType::Number(i.into())
+    } else if let Some(u) = n.as_u64() {
+        JsonValueType::Number(u.into())
+    } else {
+        match n.as_f64() {
+            Some(f) => float_value(f),
+            None => JsonValueType::String(n.to_string()),
+        }
+    }
+}
+
+/// Parse a YAML stream. A single document becomes the root; several documents are shown
+/// as the elements of a virtual top-level array.
+pub fn parse_yaml_string(yaml_string: &str) -> Result<Vec<JsonItem>, serde_yaml::Error> {
+    let mut documents = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(yaml_string) {
+        documents.push(Value::deserialize(document)?);
+    }
+
+    let mut builder = ItemBuilder::new();
+    builder.values(&documents, "document", |document, builder| {
+        parse_yaml(document, builder, None)
+    });
+    Ok(builder.finish())
+}
@@ -0,0 +1,317 @@
+use crate::json_item::{JsonItem, JsonValueType};
+use crate::json_path::{self, Comparison, Literal};
+
+const OPERATORS: [(&str, Comparison); 7] = [
+    ("<=", Comparison::LessOrEqual),
+    (">=", Comparison::GreaterOrEqual),
+    ("!=", Comparison::NotEqual),
+    ("==", Comparison::Equal),
+    ("<", Comparison::Less),
+    (">", Comparison::Greater),
+    ("=", Comparison::Equal),
+];
+
+/// Typed conditions on items like `price>100`, `deleted=true`, `type:null` or
+/// `type:array len>50`. Values are compared as what they are, so `age=3` matches the
+/// number 3 but not 30 or "3rd". Names can be preceded by a path as in `user.age=3`, and
+/// quoted strings can contain spaces as in `name="foo bar"`. All conditions must hold
+/// for an item to match.
+pub struct Predicates {
+    predicates: Vec<Predicate>,
+}
+
+enum Predicate {
+    Type(fn(&JsonValueType) -> bool),
+    /// Number of members of a container, or of characters in a string
+    Length(Comparison, f64),
+    /// The value, of the member with the given name if there is one
+    Value(Option<Name>, Comparison, Literal),
+}
+
+/// The name of a member, and the path to it if given as in `user.age`
+struct Name {
+    path: Option<String>,
+    name: String,
+}
+
+impl Predicates {
+    /// Returns `None` unless every term is a predicate, so that other search strings like
+    /// `name=some text` still search for text
+    pub fn parse(search_string: &str) -> Result<Option<Predicates>, String> {
+        let mut predicates = vec![];
+        for term in terms(search_string) {
+            match parse_term(term)? {
+                Some(predicate) => predicates.push(predicate),
+                None => return Ok(None),
+            }
+        }
+        match predicates.is_empty() {
+            true => Ok(None),
+            false => Ok(Some(Predicates { predicates })),
+        }
+    }
+
+    /// Whether the predicates say anything about the name, to highlight it
+    pub fn constrains_name(&self) -> bool {
+        self.predicates
+            .iter()
+            .any(|predicate| matches!(predicate, Predicate::Value(Some(_), ..)))
+    }
+
+    /// `breadcrumbs` are only computed for names that come with a path
+    pub fn matches(&self, item: &JsonItem, breadcrumbs: impl Fn() -> String) -> bool {
+        let is_value = !matches!(
+            item.value,
+            JsonValueType::ArrayEnd
+                | JsonValueType::ObjectEnd
+                | JsonValueType::Error(_)
+                | JsonValueType::Comment(_)
+                | JsonValueType::Unloaded(_)
+        );
+        is_value
+            && self
+                .predicates
+                .iter()
+                .all(|predicate| predicate.matches(item, &breadcrumbs))
+    }
+}
+
+impl Predicate {
+    fn matches(&self, item: &JsonItem, breadcrumbs: impl Fn() -> String) -> bool {
+        match self {
+            Predicate::Type(is_type) => is_type(&item.value),
+            Predicate::Length(comparison, len) => {
+                let item_len = match &item.value {
+                    JsonValueType::Array | JsonValueType::Object => item.len,
+                    JsonValueType::String(s) => s.chars().count(),
+                    _ => return false,
+                };
+                json_path::compare(
+                    Some(&Literal::Number(item_len as f64)),
+                    *comparison,
+                    Some(&Literal::Number(*len)),
+                )
+            }
+            Predicate::Value(name, comparison, literal) => {
+                name.as_ref()
+                    .is_none_or(|name| name.matches(item, breadcrumbs))
+                    && json_path::compare(
+                        Literal::of(&item.value).as_ref(),
+                        *comparison,
+                        Some(literal),
+                    )
+            }
+        }
+    }
+}
+
+impl Name {
+    fn parse(text: &str) -> Name {
+        match text.rsplit_once('.') {
+            Some((path, name)) if !path.is_empty() && !name.is_empty() => Name {
+                path: Some(path.to_string()),
+                name: name.to_string(),
+            },
+            _ => Name {
+                path: None,
+                name: text.to_string(),
+            },
+        }
+    }
+
+    /// Like text searches, the path only has to be part of the breadcrumbs. A name that
+    /// contains dots itself matches too.
+    fn matches(&self, item: &JsonItem, breadcrumbs: impl Fn() -> String) -> bool {
+        let name = match &item.name {
+            Some(name) => name,
+            None => return false,
+        };
+        match &self.path {
+            None => *name == self.name,
+            Some(path) if *name == format!("{}.{}", path, self.name) => true,
+            Some(path) => {
+                *name == self.name
+                    && breadcrumbs()
+                        .replace(" ▶ ", ".")
+                        .to_lowercase()
+                        .contains(&path.to_lowercase())
+            }
+        }
+    }
+}
+
+/// Split at whitespace outside of double quotes, so that `name="foo bar"` is one term
+fn terms(search_string: &str) -> Vec<&str> {
+    let mut terms = vec![];
+    let mut start = None;
+    let mut in_quotes = false;
+    for (index, c) in search_string.char_indices() {
+        if c.is_whitespace() && !in_quotes {
+            if let Some(start) = start.take() {
+                terms.push(&search_string[start..index]);
+            }
+            continue;
+        }
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+        start.get_or_insert(index);
+    }
+    if let Some(start) = start {
+        terms.push(&search_string[start..]);
+    }
+    terms
+}
+
+fn parse_term(term: &str) -> Result<Option<Predicate>, String> {
+    if let Some(type_name) = term.strip_prefix("type:") {
+        return type_test(type_name)
+            .map(|is_type| Some(Predicate::Type(is_type)))
+            .ok_or_else(|| format!("Unknown type `{}`", type_name));
+    }
+    // The leftmost operator splits the term; two-character operators come first
+    let split = term.char_indices().find_map(|(index, _)| {
+        OPERATORS
+            .iter()
+            .find(|(operator, _)| term[index..].starts_with(operator))
+            .map(|(operator, comparison)| (index, operator.len(), *comparison))
+    });
+    let (name, comparison, value) = match split {
+        Some((index, len, comparison)) => (&term[..index], comparison, &term[index + len..]),
+        None => return Ok(None),
+    };
+    let literal = match literal(value) {
+        Some(literal) => literal,
+        None => return Ok(None),
+    };
+    Ok(match (name, literal) {
+        ("len", Literal::Number(len)) => Some(Predicate::Length(comparison, len)),
+        ("", literal) => Some(Predicate::Value(None, comparison, literal)),
+        (name, literal) => Some(Predicate::Value(
+            Some(Name::parse(name)),
+            comparison,
+            literal,
+        )),
+    })
+}
+
+/// Numbers, `true`, `false`, `null` and quoted strings. Anything else is text to search for.
+fn literal(text: &str) -> Option<Literal> {
+    match text {
+        "true" => Some(Literal::Bool(true)),
+        "false" => Some(Literal::Bool(false)),
+        "null" => Some(Literal::Null),
+        _ if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') => {
+            Some(Literal::String(text[1..text.len() - 1].to_string()))
+        }
+        _ => serde_json::from_str::<serde_json::Number>(text)
+            .ok()
+            .and_then(|n| n.as_f64())
+            .map(Literal::Number),
+    }
+}
+
+fn type_test(type_name: &str) -> Option<fn(&JsonValueType) -> bool> {
+    Some(match type_name {
+        "null" => |value| matches!(value, JsonValueType::Null),
+        "bool" | "boolean" => |value| matches!(value, JsonValueType::Bool(_)),
+        "number" => |value| matches!(value, JsonValueType::Number(_)),
+        "string" => |value| matches!(value, JsonValueType::String(_)),
+        "array" => |value| matches!(value, JsonValueType::Array),
+        "object" => |value| matches!(value, JsonValueType::Object),
+        "date" | "datetime" => |value| matches!(value, JsonValueType::DateTime(_)),
+        "bytes" => |value| matches!(value, JsonValueType::Bytes(_)),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json::parse_json_string;
+    use crate::tree;
+
+    const DOCUMENT: &str = r#"{
+        "user": {"age": 3, "name": "foo bar", "tags": ["a", "b"]},
+        "config": {"enabled": true},
+        "age": 30,
+        "a.b": null
+    }"#;
+
+    fn parse(search_string: &str) -> Option<Predicates> {
+        Predicates::parse(search_string).unwrap()
+    }
+
+    /// Breadcrumbs of the items in `DOCUMENT` that match
+    fn matching(search_string: &str) -> Vec<String> {
+        let predicates = parse(search_string).expect("not a predicate");
+        let items = parse_json_string(DOCUMENT).unwrap();
+        items
+            .iter()
+            .filter(|item| predicates.matches(item, || tree::breadcrumbs(&items, item.line_number)))
+            .map(|item| tree::breadcrumbs(&items, item.line_number))
+            .collect()
+    }
+
+    #[test]
+    fn terms_are_split_outside_of_quotes() {
+        assert_eq!(terms("  a=1   b>2 "), ["a=1", "b>2"]);
+        assert_eq!(terms(r#"name="foo bar" x=1"#), [r#"name="foo bar""#, "x=1"]);
+        assert_eq!(terms(r#"="a  b""#), [r#"="a  b""#]);
+        assert!(terms(" ").is_empty());
+    }
+
+    #[test]
+    fn only_predicates_are_parsed() {
+        assert!(parse("age=3").is_some());
+        assert!(parse("type:array len>1").is_some());
+        assert!(parse(r#"name="foo bar""#).is_some());
+        assert!(parse("user.age>=3").is_some());
+        // Text searches
+        assert!(parse("").is_none());
+        assert!(parse("age").is_none());
+        assert!(parse("name=some text").is_none());
+        assert!(parse("age=3 text").is_none());
+        assert!(parse(r#"name="foo bar"#).is_none());
+    }
+
+    #[test]
+    fn unknown_types_are_errors() {
+        assert!(Predicates::parse("type:thing").is_err());
+    }
+
+    #[test]
+    fn values_are_compared_by_type() {
+        assert_eq!(matching("age=3"), ["user ▶ age"]);
+        assert_eq!(matching("age>=3"), ["user ▶ age", "age"]);
+        assert!(matching(r#"age="3""#).is_empty());
+        assert_eq!(matching("=true"), ["config ▶ enabled"]);
+        assert_eq!(matching("=null"), ["a.b"]);
+    }
+
+    #[test]
+    fn quoted_strings_can_contain_spaces() {
+        assert_eq!(matching(r#"name="foo bar""#), ["user ▶ name"]);
+        assert!(matching(r#"name="foo""#).is_empty());
+    }
+
+    #[test]
+    fn names_can_have_a_path() {
+        assert_eq!(matching("user.age=3"), ["user ▶ age"]);
+        assert_eq!(matching("config.enabled=true"), ["config ▶ enabled"]);
+        assert!(matching("config.age=3").is_empty());
+        assert!(matching("user.age=30").is_empty());
+        // A name with a dot in it
+        assert_eq!(matching("a.b=null"), ["a.b"]);
+    }
+
+    #[test]
+    fn types_and_lengths() {
+        assert_eq!(matching("type:array"), ["user ▶ tags"]);
+        assert_eq!(matching("type:object len>1"), ["", "user"]);
+        assert_eq!(
+            matching("type:string len=1"),
+            ["user ▶ tags ▶ [0]", "user ▶ tags ▶ [1]"]
+        );
+    }
+}
@@ -0,0 +1,56 @@
+/// Where the results panel is shown
+#[derive(Clone, Copy, PartialEq)]
+pub enum PanelPosition {
+    Side,
+    Bottom,
+}
+
+/// A list of all search results with their breadcrumbs and values, to see where the
+/// matches are before going to one. The selection follows `n` and `N`, and can be moved
+/// through the results without leaving the current place in the document.
+pub struct ResultsPanel {
+    pub shown: bool,
+    pub position: PanelPosition,
+    /// Whether keys move through the results instead of the document
+    pub focused: bool,
+    /// Index into the search results
+    pub selected: usize,
+    /// The first result on screen
+    pub scroll: usize,
+    /// How many results fit on screen, for paging
+    pub rows: usize,
+}
+
+impl ResultsPanel {
+    pub fn new() -> ResultsPanel {
+        ResultsPanel {
+            shown: false,
+            position: PanelPosition::Side,
+            focused: false,
+            selected: 0,
+            scroll: 0,
+            rows: 0,
+        }
+    }
+
+    pub fn select(&mut self, index: usize, num_results: usize) {
+        self.selected = index.min(num_results.saturating_sub(1));
+    }
+
+    pub fn move_to_other_side(&mut self) {
+        self.position = match self.position {
+            PanelPosition::Side => PanelPosition::Bottom,
+            PanelPosition::Bottom => PanelPosition::Side,
+        };
+    }
+
+    /// Scroll just enough for the selected result to be on screen
+    pub fn scroll_to_selected(&mut self, rows: usize) {
+        self.rows = rows;
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows.max(1);
+        }
+    }
+}
@@ -1,47 +1,311 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::background::BackgroundWork;
 use crate::json_item::JsonItem;
-use core::slice::IterMut;
+use crate::json_path::JsonPath;
+use crate::predicate::Predicates;
+use crate::tree;
 
-pub fn update_search_results(json_items: IterMut<JsonItem>, search_string: &str) {
-    let mut search_components = search_string.split("=");
-    let name_search_str = search_components.next();
-    let value_search_str = search_components.next();
+/// Items are searched in batches; results are sent and cancellation is checked after each
+const BATCH_SIZE: usize = 10_000;
 
-    let name_parts = if name_search_str.unwrap_or("").contains(".") {
-        name_search_str.unwrap().rsplit_once(".").unwrap()
-    } else {
-        ("", name_search_str.unwrap_or(""))
-    };
+/// Search strings starting with this use regular expressions
+const REGEX_PREFIX: &str = "re:";
+
+/// Search strings that are `$` or start with one of these are JSONPath expressions.
+/// Others starting with `$` are text, to find keys like `$ref`.
+const JSON_PATH_PREFIXES: [&str; 2] = ["$.", "$["];
+
+pub struct SearchResult {
+    pub line_number: usize,
+    pub name_is_search_result: bool,
+    pub value_is_search_result: bool,
+}
+
+/// A search running on a background thread. Results arrive in document order, and
+/// dropping the search cancels it.
+pub struct BackgroundSearch(BackgroundWork<Vec<SearchResult>>);
+
+impl BackgroundSearch {
+    pub fn start(items: Arc<Vec<JsonItem>>, query: Query) -> BackgroundSearch {
+        BackgroundSearch(BackgroundWork::start(move |worker| {
+            if let Query::JsonPath(path) = &query {
+                // Paths select nodes from the whole tree at once, so the results come in one go
+                let results: Vec<SearchResult> = path
+                    .evaluate(&items, worker.cancelled())
+                    .into_iter()
+                    .map(|line_number| SearchResult {
+                        line_number,
+                        name_is_search_result: items[line_number].name.is_some(),
+                        value_is_search_result: true,
+                    })
+                    .collect();
+                if !results.is_empty() {
+                    worker.send(results);
+                }
+                return;
+            }
+            for batch in items.chunks(BATCH_SIZE) {
+                if worker.is_cancelled() {
+                    return;
+                }
+                let results: Vec<SearchResult> = batch
+                    .iter()
+                    .filter_map(|item| search_item(&items, item, &query))
+                    .collect();
+                if !results.is_empty() && !worker.send(results) {
+                    return;
+                }
+            }
+        }))
+    }
+
+    /// Add the results found so far to `results`, waiting up to `timeout` for the first
+    /// ones. Returns false once the search is complete.
+    pub fn receive(&self, results: &mut Vec<SearchResult>, timeout: Duration) -> bool {
+        self.0.receive(timeout, |batch| results.extend(batch))
+    }
+}
+
+/// How text is matched. These are toggled while searching and kept for the session.
+#[derive(Clone, Copy, Default)]
+pub struct SearchOptions {
+    /// Ignore case unless the search text has capital letters, like `smartcase` in vim
+    pub smart_case: bool,
+    pub whole_word: bool,
+    /// Match the whole name or value
+    pub exact: bool,
+    /// Search only names with the whole search text, without splitting at `=`
+    pub keys_only: bool,
+    /// Search only values with the whole search text, without splitting at `=`
+    pub values_only: bool,
+}
 
-    for item in json_items {
-        item.name_is_search_result = search_in_name(&item.name, &item.breadcrumbs, name_parts);
-        item.value_is_search_result = search_in_value(&item.value_str, value_search_str);
+impl SearchOptions {
+    /// The options that are on, e.g. ` [smart case] [keys]`
+    pub fn indicators(&self) -> String {
+        [
+            (self.smart_case, "smart case"),
+            (self.whole_word, "word"),
+            (self.exact, "exact"),
+            (self.keys_only, "keys"),
+            (self.values_only, "values"),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, name)| format!(" [{}]", name))
+        .collect()
+    }
 
-        // name_search_str != "" && value_search_str != "": only match if both are search results
-        if not_empty(name_search_str) && not_empty(value_search_str) {
-            if !(item.name_is_search_result && item.value_is_search_result) {
-                item.name_is_search_result = false;
-                item.value_is_search_result = false;
+    fn case_sensitive(&self, search_string: &str) -> bool {
+        self.smart_case && search_string.chars().any(char::is_uppercase)
+    }
+
+    /// The name and value parts of the search text
+    fn split<'a>(&self, search_string: &'a str) -> (Option<&'a str>, Option<&'a str>) {
+        match (self.keys_only, self.values_only) {
+            (true, _) => (Some(search_string), None),
+            (_, true) => (None, Some(search_string)),
+            // Only the first `=` separates the name from the value
+            _ => match search_string.split_once('=') {
+                Some((name, value)) => (Some(name), Some(value)),
+                None => (Some(search_string), None),
+            },
+        }
+    }
+}
+
+/// What to look for. Plain search strings have the form `name=value`,
+/// `breadcrumbs.name=value`, `name` or `=value` and match case-insensitive substrings.
+/// With the `re:` prefix, the name and value are regular expressions instead, and
+/// search strings starting with `$.` or `$[` are JSONPath expressions selecting whole
+/// nodes.
+/// Search strings made up of typed predicates like `price>100 deleted=false` compare
+/// values by type, unless only keys, only values or exact matches are searched.
+pub enum Query {
+    Text {
+        name_search_str: Option<String>,
+        value_search_str: Option<String>,
+        matcher: TextMatcher,
+    },
+    Regex {
+        name: Option<Regex>,
+        value: Option<Regex>,
+    },
+    JsonPath(JsonPath),
+    Predicates(Predicates),
+}
+
+impl Query {
+    /// Errors are messages for the status bar
+    pub fn parse(search_string: &str, options: SearchOptions) -> Result<Query, String> {
+        if search_string == "$"
+            || JSON_PATH_PREFIXES
+                .iter()
+                .any(|prefix| search_string.starts_with(prefix))
+        {
+            return JsonPath::parse(search_string)
+                .map(Query::JsonPath)
+                .map_err(|error| format!("Invalid JSONPath: {}", error));
+        }
+        if let Some(pattern) = search_string.strip_prefix(REGEX_PREFIX) {
+            let (name, value) = options.split(pattern);
+            return Ok(Query::Regex {
+                name: compile(name, options)?,
+                value: compile(value, options)?,
+            });
+        }
+        // These options ask for the text as typed, e.g. `a>1` as a key
+        let literal = options.keys_only || options.values_only || options.exact;
+        if !literal {
+            if let Some(predicates) = Predicates::parse(search_string)? {
+                return Ok(Query::Predicates(predicates));
             }
         }
+        let (name, value) = options.split(search_string);
+        Ok(Query::Text {
+            name_search_str: name.map(str::to_string),
+            value_search_str: value.map(str::to_string),
+            matcher: TextMatcher {
+                case_sensitive: options.case_sensitive(search_string),
+                whole_word: options.whole_word,
+                exact: options.exact,
+            },
+        })
+    }
+}
+
+/// Empty patterns match nothing rather than everything
+fn compile(pattern: Option<&str>, options: SearchOptions) -> Result<Option<Regex>, String> {
+    let pattern = match pattern {
+        None | Some("") => return Ok(None),
+        Some(pattern) if options.exact => format!("^(?:{})$", pattern),
+        Some(pattern) if options.whole_word => format!(r"\b(?:{})\b", pattern),
+        Some(pattern) => pattern.to_string(),
+    };
+    // Regular expressions are case-sensitive unless smart case finds no capitals
+    let case_insensitive = options.smart_case && !options.case_sensitive(&pattern);
+    RegexBuilder::new(&pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+        .map(Some)
+        .map_err(|error| {
+            // The last line of the message says what is wrong, without the pattern
+            let message = error.to_string();
+            let last_line = message.lines().last().unwrap_or_default();
+            format!("Invalid regex: {}", last_line.trim_start_matches("error: "))
+        })
+}
+
+/// Compares plain search text with names and values
+pub struct TextMatcher {
+    case_sensitive: bool,
+    whole_word: bool,
+    exact: bool,
+}
+
+impl TextMatcher {
+    fn matches(&self, text: &str, pattern: &str) -> bool {
+        let (text, pattern) = match self.case_sensitive {
+            true => (text.to_string(), pattern.to_string()),
+            false => (text.to_lowercase(), pattern.to_lowercase()),
+        };
+        if self.exact {
+            return text == pattern;
+        }
+        if !self.whole_word {
+            return text.contains(&pattern);
+        }
+        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+        text.match_indices(&pattern).any(|(start, found)| {
+            let end = start + found.len();
+            !text[..start].chars().next_back().is_some_and(is_word_char)
+                && !text[end..].chars().next().is_some_and(is_word_char)
+        })
+    }
+}
+
+fn search_item(items: &[JsonItem], item: &JsonItem, query: &Query) -> Option<SearchResult> {
+    let (name_is_search_result, value_is_search_result, both_required) = match query {
+        Query::Text {
+            name_search_str,
+            value_search_str,
+            matcher,
+        } => {
+            let name_search_str = name_search_str.as_deref();
+            let value_search_str = value_search_str.as_deref();
+            let name_parts = if name_search_str.unwrap_or("").contains(".") {
+                name_search_str.unwrap().rsplit_once(".").unwrap()
+            } else {
+                ("", name_search_str.unwrap_or(""))
+            };
+            (
+                search_in_name(&item.name, name_parts, matcher, || {
+                    tree::breadcrumbs(items, item.line_number)
+                }),
+                search_in_value(&item.value_str, value_search_str, matcher),
+                // name_search_str != "" && value_search_str != "": only match if both are search results
+                not_empty(name_search_str) && not_empty(value_search_str),
+            )
+        }
+        Query::Regex { name, value } => (
+            name.as_ref()
+                .zip(item.name.as_ref())
+                .is_some_and(|(regex, name)| regex.is_match(name)),
+            value
+                .as_ref()
+                .is_some_and(|regex| !item.value_str.is_empty() && regex.is_match(&item.value_str)),
+            name.is_some() && value.is_some(),
+        ),
+        Query::Predicates(predicates) => {
+            let matches = predicates.matches(item, || tree::breadcrumbs(items, item.line_number));
+            (
+                matches && item.name.is_some() && predicates.constrains_name(),
+                matches,
+                false,
+            )
+        }
+        Query::JsonPath(_) => unreachable!("JSONPath queries are evaluated on the whole tree"),
+    };
+
+    if both_required && !(name_is_search_result && value_is_search_result) {
+        return None;
+    }
+    match name_is_search_result || value_is_search_result {
+        true => Some(SearchResult {
+            line_number: item.line_number,
+            name_is_search_result,
+            value_is_search_result,
+        }),
+        false => None,
     }
 }
 
-fn search_in_name(name: &Option<String>, breadcrumbs: &str, name_parts: (&str, &str)) -> bool {
+/// Breadcrumbs are only computed for items whose name matches
+fn search_in_name(
+    name: &Option<String>,
+    name_parts: (&str, &str),
+    matcher: &TextMatcher,
+    breadcrumbs: impl FnOnce() -> String,
+) -> bool {
     match (name, name_parts) {
-        (Some(n), ("", s)) => n.to_lowercase().contains(&s.to_lowercase()) && !s.is_empty(),
+        (Some(n), ("", s)) => matcher.matches(n, s) && !s.is_empty(),
+        // The breadcrumbs only have to contain the path before the name
         (Some(n), (bs, ns)) => {
-            n.to_lowercase().contains(&ns.to_lowercase())
-                && breadcrumbs.contains(&bs.to_lowercase())
+            matcher.matches(n, ns) && breadcrumbs().to_lowercase().contains(&bs.to_lowercase())
         }
         _ => false,
     }
 }
 
-fn search_in_value(value: &String, search_str: Option<&str>) -> bool {
+fn search_in_value(value: &str, search_str: Option<&str>, matcher: &TextMatcher) -> bool {
     match search_str {
         Some("") => false,
         Some("*") => !value.is_empty(),
-        Some(s) => value.to_lowercase().contains(&s.to_lowercase()) && !value.is_empty(),
+        Some(s) => matcher.matches(value, s) && !value.is_empty(),
         None => false,
     }
 }
@@ -52,3 +316,52 @@ fn not_empty(s: Option<&str>) -> bool {
         Some(st) => !st.is_empty(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_paths_starting_with_dollar_dot_or_bracket_are_json_path() {
+        let is_json_path = |search_string: &str| {
+            matches!(
+                Query::parse(search_string, SearchOptions::default()),
+                Ok(Query::JsonPath(_))
+            )
+        };
+        assert!(is_json_path("$"));
+        assert!(is_json_path("$.a"));
+        assert!(is_json_path("$..a"));
+        assert!(is_json_path("$['a']"));
+        assert!(!is_json_path("$ref"));
+        assert!(!is_json_path("$schema=x"));
+        assert!(Query::parse("$.", SearchOptions::default()).is_err());
+    }
+
+    #[test]
+    fn text_options_search_for_predicates_as_text() {
+        let is_predicates = |search_string: &str, options: SearchOptions| {
+            matches!(
+                Query::parse(search_string, options),
+                Ok(Query::Predicates(_))
+            )
+        };
+        assert!(is_predicates("price>100", SearchOptions::default()));
+        let keys_only = SearchOptions {
+            keys_only: true,
+            ..SearchOptions::default()
+        };
+        let values_only = SearchOptions {
+            values_only: true,
+            ..SearchOptions::default()
+        };
+        let exact = SearchOptions {
+            exact: true,
+            ..SearchOptions::default()
+        };
+        for options in [keys_only, values_only, exact] {
+            assert!(!is_predicates("price>100", options));
+            assert!(!is_predicates("deleted=false", options));
+        }
+    }
+}
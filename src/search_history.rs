@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+use std::{env, fs, io, process};
+
+/// Older searches are forgotten
+const MAX_ENTRIES: usize = 1000;
+
+/// Past search texts, oldest first, kept in `$XDG_STATE_HOME/jex/search_history` (with
+/// `~/.local/state` as the default on every platform) so that they survive restarts.
+/// Going through the history remembers the text that was being typed, and can be
+/// limited to entries containing some text, like Ctrl-R in a shell.
+pub struct SearchHistory {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+    /// The entry being shown, while going through the history
+    position: Option<usize>,
+    /// What was typed before going through the history
+    draft: String,
+    /// Only entries containing this are recalled
+    needle: Option<String>,
+}
+
+impl SearchHistory {
+    /// The history is a convenience, so a missing or unreadable file gives an empty one
+    pub fn load() -> SearchHistory {
+        let path = state_dir().map(|dir| dir.join("jex").join("search_history"));
+        let entries = path.as_deref().and_then(read_entries).unwrap_or_default();
+        SearchHistory {
+            entries,
+            path,
+            position: None,
+            draft: String::new(),
+            needle: None,
+        }
+    }
+
+    /// Remember a search, moving it to the end if it was there already, and save the history
+    pub fn add(&mut self, search_string: &str) {
+        self.stop_browsing();
+        if search_string.is_empty() {
+            return;
+        }
+        // Other instances may have saved searches since this one started
+        if let Some(entries) = self.path.as_deref().and_then(read_entries) {
+            self.entries = entries;
+        }
+        self.entries.retain(|entry| entry != search_string);
+        self.entries.push(search_string.to_string());
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+        }
+        if let Some(path) = &self.path {
+            // Failing to save only loses the history
+            let _ = save(path, &self.entries);
+        }
+    }
+
+    /// Forget where we are in the history, after the search text was edited
+    pub fn stop_browsing(&mut self) {
+        self.position = None;
+        self.needle = None;
+    }
+
+    /// The next older entry, if there is one. `current` is the text in the search box.
+    pub fn previous(&mut self, current: &str) -> Option<String> {
+        let end = match self.position {
+            Some(position) => position,
+            None => {
+                self.draft = current.to_string();
+                self.entries.len()
+            }
+        };
+        let position = self.entries[..end]
+            .iter()
+            .rposition(|entry| self.is_recalled(entry))?;
+        self.position = Some(position);
+        Some(self.entries[position].clone())
+    }
+
+    /// The next newer entry, or what was typed before going through the history
+    pub fn next(&mut self) -> Option<String> {
+        let start = self.position? + 1;
+        match self.entries[start..]
+            .iter()
+            .position(|entry| self.is_recalled(entry))
+        {
+            Some(offset) => {
+                self.position = Some(start + offset);
+                Some(self.entries[start + offset].clone())
+            }
+            None => {
+                self.position = None;
+                Some(self.draft.clone())
+            }
+        }
+    }
+
+    /// The next older entry containing the text that was typed when this was first used
+    pub fn search(&mut self, current: &str) -> Option<String> {
+        if self.needle.is_none() {
+            self.needle = Some(match self.position {
+                Some(_) => self.draft.clone(),
+                None => current.to_string(),
+            });
+        }
+        self.previous(current)
+    }
+
+    /// The text that entries are searched for, while searching the history
+    pub fn needle(&self) -> Option<&str> {
+        self.needle.as_deref()
+    }
+
+    fn is_recalled(&self, entry: &str) -> bool {
+        self.needle
+            .as_ref()
+            .is_none_or(|needle| entry.contains(needle.as_str()))
+    }
+}
+
+fn state_dir() -> Option<PathBuf> {
+    match env::var_os("XDG_STATE_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => Some(dir),
+        _ => dirs::home_dir().map(|home| home.join(".local").join("state")),
+    }
+}
+
+fn read_entries(path: &Path) -> Option<Vec<String>> {
+    let text = fs::read_to_string(path).ok()?;
+    Some(text.lines().map(str::to_string).collect())
+}
+
+/// Write a temporary file and move it into place, so that the history is never left
+/// half written
+fn save(path: &Path, entries: &[String]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp_path = path.with_extension(format!("{}.tmp", process::id()));
+    fs::write(&temp_path, entries.join("\n") + "\n")?;
+    fs::rename(&temp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })
+}
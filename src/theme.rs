@@ -6,8 +6,14 @@ pub struct Theme {
     pub number_color: Color,
     pub bool_color: Color,
     pub null_color: Color,
+    pub date_color: Color,
+    pub bytes_color: Color,
+    pub tag_color: Color,
+    pub error_color: Color,
+    pub comment_color: Color,
     pub selection_level_indicator_color: Color,
     pub selection_indicator_color: Color,
+    #[allow(dead_code)]
     pub selection_background_color: Color,
     pub indent_color: Color,
     pub search_indicator_color: Color,
@@ -15,12 +21,18 @@ pub struct Theme {
     pub status_text_color: Color,
 }
 
+#[allow(dead_code)]
 const DARK_THEME: Theme = Theme {
     name_color: Color::White,
     string_color: Color::Yellow,
     number_color: Color::LightBlue,
     bool_color: Color::Cyan,
     null_color: Color::Red,
+    date_color: Color::LightGreen,
+    bytes_color: Color::LightCyan,
+    tag_color: Color::Gray,
+    error_color: Color::LightRed,
+    comment_color: Color::DarkGray,
     selection_level_indicator_color: Color::Cyan,
     selection_indicator_color: Color::Magenta,
     selection_background_color: Color::DarkGray,
@@ -36,6 +48,11 @@ const LIGHT_THEME: Theme = Theme {
     number_color: Color::Green,
     bool_color: Color::Cyan,
     null_color: Color::Red,
+    date_color: Color::Magenta,
+    bytes_color: Color::Blue,
+    tag_color: Color::Gray,
+    error_color: Color::LightRed,
+    comment_color: Color::Gray,
     selection_level_indicator_color: Color::Cyan,
     selection_indicator_color: Color::Magenta,
     selection_background_color: Color::DarkGray,
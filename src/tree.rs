@@ -0,0 +1,167 @@
+use crate::json_item::{JsonItem, JsonValueType};
+
+/// The container an item belongs to, i.e. the opening bracket for closing brackets and
+/// the item itself for containers
+pub fn enclosing_container(items: &[JsonItem], index: usize) -> Option<usize> {
+    let item = &items[index];
+    match item.value {
+        JsonValueType::Array | JsonValueType::Object => Some(index),
+        JsonValueType::ArrayEnd | JsonValueType::ObjectEnd => item.bracket,
+        _ => item.parent,
+    }
+}
+
+/// The root value of the document: the first top-level item that isn't a comment or
+/// error, e.g. after a leading comment in JSONC
+pub fn root(items: &[JsonItem]) -> Option<usize> {
+    items.iter().position(|item| {
+        item.parent.is_none()
+            && !matches!(
+                item.value,
+                JsonValueType::Error(_) | JsonValueType::Comment(_) | JsonValueType::Unloaded(_)
+            )
+    })
+}
+
+/// Whether `index` lies between the brackets of the container `ancestor`
+pub fn is_inside(items: &[JsonItem], ancestor: usize, index: usize) -> bool {
+    match items[ancestor].bracket {
+        Some(end) => ancestor < index && index < end,
+        None => false,
+    }
+}
+
+/// The JSONPath of an item, e.g. `$.items[3].name` or `$['odd key']`, which selects it in
+/// a search. Closing brackets and annotations have the path of their container.
+pub fn json_path(items: &[JsonItem], index: usize) -> String {
+    let mut segments = vec![];
+    let mut next = owner(items, index);
+    while let Some(index) = next {
+        let item = &items[index];
+        let parent = item.parent.map(|parent| &items[parent]);
+        match (parent, &item.name) {
+            (None, _) => {}
+            (Some(parent), _) if parent.value == JsonValueType::Array => {
+                segments.push(format!("[{}]", item.index_in_parent));
+            }
+            (Some(_), Some(name)) if is_identifier(name) => segments.push(format!(".{}", name)),
+            (Some(_), name) => {
+                let name = name.as_deref().unwrap_or_default();
+                segments.push(format!("['{}']", escape(name)));
+            }
+        }
+        next = item.parent;
+    }
+    segments.reverse();
+    format!("${}", segments.concat())
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && name.starts_with(|c: char| !c.is_ascii_digit())
+}
+
+/// Quote a member name for a bracketed JSONPath selector
+fn escape(name: &str) -> String {
+    let mut escaped = String::new();
+    for c in name.chars() {
+        match c {
+            '\\' | '\'' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The item that closing brackets and annotations belong to, the item itself otherwise
+fn owner(items: &[JsonItem], index: usize) -> Option<usize> {
+    let item = &items[index];
+    match item.value {
+        JsonValueType::ArrayEnd | JsonValueType::ObjectEnd => item.bracket,
+        JsonValueType::Error(_) | JsonValueType::Comment(_) | JsonValueType::Unloaded(_) => {
+            item.parent
+        }
+        _ => Some(index),
+    }
+}
+
+/// The path to an item, e.g. `items ▶ [3] ▶ name`. Closing brackets and annotations
+/// share the breadcrumbs of the container they belong to.
+pub fn breadcrumbs(items: &[JsonItem], index: usize) -> String {
+    let mut crumbs = vec![];
+    let mut next = owner(items, index);
+    while let Some(index) = next {
+        let item = &items[index];
+        let parent = item.parent.map(|parent| &items[parent]);
+        let crumb = match (&item.label, parent) {
+            (Some(label), _) => label.clone(),
+            (None, Some(parent)) if parent.value == JsonValueType::Array => {
+                item.index_in_parent.to_string()
+            }
+            (None, _) => item.name.clone().unwrap_or_default(),
+        };
+        let in_array = parent.is_some_and(|parent| parent.value == JsonValueType::Array);
+        crumbs.push((crumb, in_array));
+        next = item.parent;
+    }
+
+    let mut text = String::new();
+    for (crumb, in_array) in crumbs.into_iter().rev() {
+        if text.is_empty() {
+            text = crumb;
+        } else if in_array {
+            text = format!("{} ▶ [{}]", text, crumb);
+        } else {
+            text = format!("{} ▶ {}", text, crumb);
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json_path::JsonPath;
+    use crate::parse_json::parse_json_string;
+
+    #[test]
+    fn json_paths_select_their_item() {
+        let text = r#"{"a": [1, {"b": null}], "odd key": {"it's": [], "": 2, "1x": 3}, "é": 4}"#;
+        let items = parse_json_string(text).unwrap();
+        let paths: Vec<String> = (0..items.len())
+            .map(|line| json_path(&items, line))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "$",
+                "$.a",
+                "$.a[0]",
+                "$.a[1]",
+                "$.a[1].b",
+                "$.a[1]",
+                "$.a",
+                "$['odd key']",
+                "$['odd key']['it\\'s']",
+                "$['odd key']['it\\'s']",
+                "$['odd key']['']",
+                "$['odd key']['1x']",
+                "$['odd key']",
+                "$.é",
+                "$"
+            ]
+        );
+        for (line, path) in paths.iter().enumerate() {
+            let owner = owner(&items, line).unwrap();
+            let selected = JsonPath::parse(path)
+                .unwrap()
+                .evaluate(&items, &Default::default());
+            assert_eq!(selected, [owner], "{}", path);
+        }
+    }
+}
@@ -1,24 +1,114 @@
 use std::io;
+use std::thread::JoinHandle;
+use std::time::Duration;
 
-use crossterm::event::{self, Event, KeyCode};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
 use ratatui::{backend::Backend, style::Style, widgets::Block};
 use ratatui::{prelude::*, widgets::*};
 use ratatui::{Frame, Terminal};
 
 use crate::app_state::AppState;
 use crate::app_state::SearchState;
+use crate::fuzzy_finder::{self, FuzzyFinder};
+use crate::json_item::JsonItem;
+use crate::load::Progress;
+use crate::results_panel::PanelPosition;
 use crate::theme::THEME;
+use crate::tree;
+use thousands::Separable;
+
+const SEARCH_REDRAW_INTERVAL: Duration = Duration::from_millis(50);
 
 pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, app_state: &mut AppState) -> io::Result<()> {
     loop {
+        app_state.receive_search_results(Duration::ZERO);
+        app_state.receive_filter_output(Duration::ZERO);
+        if let Some(fuzzy_finder) = &mut app_state.fuzzy_finder {
+            fuzzy_finder.receive_matches(Duration::ZERO);
+        }
         terminal.draw(|frame| render(frame, app_state))?;
 
+        // Keep redrawing while results come in
+        let is_running = app_state.is_search_running()
+            || app_state.is_filter_running()
+            || app_state
+                .fuzzy_finder
+                .as_ref()
+                .is_some_and(FuzzyFinder::is_running);
+        if is_running && !event::poll(SEARCH_REDRAW_INTERVAL)? {
+            continue;
+        }
         if let Event::Key(key) = event::read()? {
+            if let Some(fuzzy_finder) = &mut app_state.fuzzy_finder {
+                match key.code {
+                    KeyCode::Enter => {
+                        app_state.finish_fuzzy_finder();
+                    }
+                    KeyCode::Esc => {
+                        app_state.fuzzy_finder = None;
+                    }
+                    KeyCode::Down => {
+                        fuzzy_finder.select_next();
+                    }
+                    KeyCode::Up => {
+                        fuzzy_finder.select_previous();
+                    }
+                    _ => {
+                        app_state.update_fuzzy_finder(&Event::Key(key));
+                    }
+                }
+                continue;
+            }
+            if app_state.editing_filter {
+                match key.code {
+                    KeyCode::Enter => {
+                        app_state.finish_filtering();
+                    }
+                    KeyCode::Esc => {
+                        app_state.cancel_filtering();
+                    }
+                    _ => {
+                        app_state.update_filter(&Event::Key(key));
+                    }
+                }
+                continue;
+            }
             match app_state.search_state {
+                SearchState::Searching if key.modifiers.contains(KeyModifiers::ALT) => {
+                    match key.code {
+                        KeyCode::Char('c') => app_state.toggle_search_option(|options| {
+                            options.smart_case = !options.smart_case;
+                        }),
+                        KeyCode::Char('w') => app_state.toggle_search_option(|options| {
+                            options.whole_word = !options.whole_word;
+                        }),
+                        KeyCode::Char('e') => app_state.toggle_search_option(|options| {
+                            options.exact = !options.exact;
+                        }),
+                        KeyCode::Char('k') => app_state.toggle_search_option(|options| {
+                            options.keys_only = !options.keys_only;
+                            options.values_only = false;
+                        }),
+                        KeyCode::Char('v') => app_state.toggle_search_option(|options| {
+                            options.values_only = !options.values_only;
+                            options.keys_only = false;
+                        }),
+                        _ => {}
+                    }
+                }
                 SearchState::Searching => match key.code {
                     KeyCode::Enter => {
                         app_state.finish_searching();
                     }
+                    KeyCode::Up => {
+                        app_state.recall_previous_search();
+                    }
+                    KeyCode::Down => {
+                        app_state.recall_next_search();
+                    }
+                    KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app_state.recall_matching_search();
+                    }
                     KeyCode::Esc => {
                         app_state.cancel_searching();
                     }
@@ -26,6 +116,55 @@ pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, app_state: &mut AppState)
                         app_state.update_search(&Event::Key(key));
                     }
                 },
+                SearchState::NotSearching | SearchState::BrowsingSearch(_)
+                    if app_state.results_panel.focused =>
+                {
+                    match key.code {
+                        KeyCode::Char('q') => return Ok(()),
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            app_state.select_next_in_results_panel(1);
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            app_state.select_previous_in_results_panel(1);
+                        }
+                        KeyCode::Char(' ') | KeyCode::PageDown => {
+                            let rows = app_state.results_panel.rows.max(1);
+                            app_state.select_next_in_results_panel(rows);
+                        }
+                        KeyCode::Backspace | KeyCode::PageUp => {
+                            let rows = app_state.results_panel.rows.max(1);
+                            app_state.select_previous_in_results_panel(rows);
+                        }
+                        KeyCode::Char('g') => {
+                            app_state.select_previous_in_results_panel(usize::MAX);
+                        }
+                        KeyCode::Char('G') => {
+                            app_state.select_next_in_results_panel(usize::MAX);
+                        }
+                        KeyCode::Enter => {
+                            app_state.finish_results_panel();
+                        }
+                        KeyCode::Tab | KeyCode::Esc => {
+                            app_state.focus_results_panel(false);
+                        }
+                        KeyCode::Char('n') => {
+                            app_state.next_search_result();
+                        }
+                        KeyCode::Char('N') => {
+                            app_state.previous_search_result();
+                        }
+                        KeyCode::Char('r') => {
+                            app_state.toggle_results_panel();
+                        }
+                        KeyCode::Char('R') => {
+                            app_state.results_panel.move_to_other_side();
+                        }
+                        KeyCode::Char('/') => {
+                            app_state.start_searching();
+                        }
+                        _ => {}
+                    }
+                }
                 SearchState::NotSearching | SearchState::BrowsingSearch(_) => match key.code {
                     KeyCode::Char('n') => {
                         app_state.next_search_result();
@@ -93,6 +232,30 @@ pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, app_state: &mut AppState)
                     KeyCode::Char('*') => {
                         app_state.start_searching_for_name();
                     }
+                    KeyCode::Char('$') => {
+                        app_state.start_searching_for_path();
+                    }
+                    KeyCode::Char('|') => {
+                        app_state.start_filtering();
+                    }
+                    KeyCode::Char('o') => {
+                        app_state.toggle_filter_output();
+                    }
+                    KeyCode::Char('&') => {
+                        app_state.toggle_matches_only();
+                    }
+                    KeyCode::Char('f') => {
+                        app_state.open_fuzzy_finder();
+                    }
+                    KeyCode::Char('r') => {
+                        app_state.toggle_results_panel();
+                    }
+                    KeyCode::Char('R') => {
+                        app_state.results_panel.move_to_other_side();
+                    }
+                    KeyCode::Tab => {
+                        app_state.focus_results_panel(true);
+                    }
                     _ => {}
                 },
             }
@@ -100,12 +263,83 @@ pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, app_state: &mut AppState)
     }
 }
 
+/// Show a progress bar until the loading thread is done. Returns false if the user pressed
+/// `q` to give up.
+pub fn show_progress<B: Backend, T>(
+    terminal: &mut Terminal<B>,
+    loader: &JoinHandle<T>,
+    progress: &Progress,
+    filename: &str,
+) -> io::Result<bool> {
+    while !loader.is_finished() {
+        terminal.draw(|frame| render_progress(frame, progress, filename))?;
+
+        if event::poll(Duration::from_millis(100))? {
+            if let Event::Key(key) = event::read()? {
+                if let KeyCode::Char('q') | KeyCode::Esc = key.code {
+                    return Ok(false);
+                }
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn render_progress(frame: &mut Frame, progress: &Progress, filename: &str) {
+    let (stage, done, total) = progress.get();
+    let (ratio, label) = match (done, total) {
+        (0, 0) => (0.0, stage.to_string()),
+        (_, 0) => (0.0, format!("{} {}", stage, megabytes(done))),
+        _ => (
+            (done as f64 / total as f64).min(1.0),
+            format!("{} {} of {}", stage, megabytes(done), megabytes(total)),
+        ),
+    };
+
+    let size = frame.size();
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage(50),
+            Constraint::Length(3),
+            Constraint::Min(0),
+        ])
+        .split(size);
+    let gauge = Gauge::default()
+        .block(
+            Block::default()
+                .title(format!("{} (q to quit)", filename))
+                .borders(Borders::ALL),
+        )
+        .gauge_style(Style::default().fg(THEME.selection_indicator_color))
+        .ratio(ratio)
+        .label(label);
+    frame.render_widget(gauge, chunks[1]);
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+}
+
 fn render(frame: &mut Frame, app_state: &mut AppState) {
     // Layout
     let size = frame.size();
 
-    let chunks = match app_state.search_state {
-        SearchState::Searching | SearchState::BrowsingSearch(_) => Layout::default()
+    // The search or filter text is edited in a box above the list
+    let input_title = match app_state.search_state {
+        _ if app_state.editing_filter => Some("Filter:".to_string()),
+        SearchState::Searching | SearchState::BrowsingSearch(_) => Some(format!(
+            "Search:{}{}",
+            app_state.search_options.indicators(),
+            match app_state.search_history.needle() {
+                Some(needle) => format!(" [history: {}]", needle),
+                None => "".to_string(),
+            }
+        )),
+        SearchState::NotSearching => None,
+    };
+    let chunks = match input_title {
+        Some(_) => Layout::default()
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Length(3),
@@ -118,16 +352,30 @@ fn render(frame: &mut Frame, app_state: &mut AppState) {
             .constraints([Constraint::Min(5), Constraint::Length(3)])
             .split(size),
     };
-    let list_chunk = match app_state.search_state {
-        SearchState::Searching => chunks[1],
-        SearchState::BrowsingSearch(_) => chunks[1],
-        _ => chunks[0],
+    let (list_chunk, bottom_chunk) = match input_title {
+        Some(_) => (chunks[1], chunks[2]),
+        None => (chunks[0], chunks[1]),
     };
-    app_state.list_height = list_chunk.height - 1;
-    let bottom_chunk = match app_state.search_state {
-        SearchState::Searching | SearchState::BrowsingSearch(_) => chunks[2],
-        _ => chunks[1],
+    // The results panel takes part of the list's space
+    let (list_chunk, results_chunk) = match app_state.results_panel.shown {
+        true => {
+            let direction = match app_state.results_panel.position {
+                PanelPosition::Side => Direction::Horizontal,
+                PanelPosition::Bottom => Direction::Vertical,
+            };
+            let parts = Layout::default()
+                .direction(direction)
+                .constraints([Constraint::Min(5), Constraint::Percentage(40)])
+                .split(list_chunk);
+            (parts[0], Some(parts[1]))
+        }
+        false => (list_chunk, None),
     };
+    app_state.list_height = list_chunk.height - 1;
+    app_state.load_items_in_view();
+    if let Some(results_chunk) = results_chunk {
+        app_state.set_results_panel_rows(results_chunk.height.saturating_sub(2) as usize);
+    }
 
     let bottom_layout = Layout::default()
         .direction(Direction::Horizontal)
@@ -150,42 +398,165 @@ fn render(frame: &mut Frame, app_state: &mut AppState) {
 
     // Main view
     let display_items = app_state.display_items();
-    let selection_index = app_state.selection_index();
 
     let list_items: Vec<Line> = display_items
         .iter()
-        .map(|item| item.display_text(selection_index))
+        .map(|(item, state)| item.display_text(state))
         .collect();
     let list = Paragraph::new(list_items).block(
         Block::default()
-            .title(app_state.filename.clone())
+            .title(app_state.title())
             .borders(Borders::TOP),
     );
 
-    // Search
-    let search = Paragraph::new(app_state.search_text().to_string().clone())
+    // Search or filter
+    let input = match app_state.editing_filter {
+        true => &app_state.filter_input,
+        false => &app_state.search_input,
+    };
+    let input_box = Paragraph::new(input.value().to_string())
         .style(Style::default())
-        .block(Block::default().borders(Borders::ALL).title("Search:"));
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(input_title.clone().unwrap_or_default()),
+        );
 
     // Render
     frame.render_widget(list, list_chunk);
     frame.render_widget(breadbrumbs, bottom_layout[0]);
     frame.render_widget(status_area, bottom_layout[1]);
-    match app_state.search_state {
-        SearchState::Searching | SearchState::BrowsingSearch(_) => {
-            frame.render_widget(search, chunks[0])
-        }
-        _ => {}
+    if input_title.is_some() {
+        frame.render_widget(input_box, chunks[0]);
+    }
+    if let Some(results_chunk) = results_chunk {
+        render_results_panel(frame, app_state, results_chunk);
+    }
+    if let Some(fuzzy_finder) = &app_state.fuzzy_finder {
+        render_fuzzy_finder(frame, fuzzy_finder, &app_state.items);
+        return;
     }
 
     // Place cursor
     let width = size.width.max(3) - 3; // keep 2 for borders and 1 for cursor
-    let scroll = app_state.search_input.visual_scroll(width as usize);
+    let scroll = input.visual_scroll(width as usize);
     let cursor_y = 1;
-    if app_state.search_state == SearchState::Searching {
+    if app_state.editing_filter || app_state.search_state == SearchState::Searching {
         frame.set_cursor(
-            ((app_state.search_input.visual_cursor()).max(scroll) - scroll) as u16 + 1,
+            ((input.visual_cursor()).max(scroll) - scroll) as u16 + 1,
             cursor_y as u16,
         )
     }
 }
+
+/// All search results, one per line with its breadcrumbs and value
+fn render_results_panel(frame: &mut Frame, app_state: &AppState, area: Rect) {
+    let num_results = app_state.search_results().len();
+    let panel = &app_state.results_panel;
+    let title = match app_state.is_search_running() {
+        true => format!("Results ({} so far…)", num_results.separate_with_commas()),
+        false => format!("Results ({})", num_results.separate_with_commas()),
+    };
+    let lines: Vec<Line> = app_state
+        .search_results()
+        .iter()
+        .enumerate()
+        .skip(panel.scroll)
+        .take(panel.rows)
+        .map(|(index, result)| {
+            let item = &app_state.items[result.line_number];
+            Line::from(vec![
+                match index == panel.selected {
+                    true => {
+                        Span::styled("▶ ", Style::default().fg(THEME.selection_indicator_color))
+                    }
+                    false => Span::raw("  "),
+                },
+                Span::styled(
+                    tree::breadcrumbs(&app_state.items, result.line_number),
+                    Style::default().fg(THEME.name_color),
+                ),
+                Span::styled(
+                    format!("  {}", fuzzy_finder::value_preview(item)),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ])
+        })
+        .collect();
+    let border_style = match panel.focused {
+        true => Style::default().fg(THEME.selection_indicator_color),
+        false => Style::default(),
+    };
+    let list = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(border_style)
+            .title(title),
+    );
+    frame.render_widget(list, area);
+}
+
+/// A popup in the middle of the screen, with the typed text above the ranked keys
+fn render_fuzzy_finder(frame: &mut Frame, fuzzy_finder: &FuzzyFinder, items: &[JsonItem]) {
+    let size = frame.size();
+    let width = (size.width * 4 / 5).max(3).min(size.width);
+    let height = (size.height * 7 / 10).max(6).min(size.height);
+    let area = Rect::new(
+        (size.width - width) / 2,
+        (size.height - height) / 2,
+        width,
+        height,
+    );
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(3)])
+        .split(area);
+
+    let title = match fuzzy_finder.is_running() {
+        true => "Find path: (searching…)".to_string(),
+        false => format!("Find path: ({} matches)", fuzzy_finder.matches.len()),
+    };
+    let input = Paragraph::new(fuzzy_finder.input.value().to_string())
+        .block(Block::default().borders(Borders::ALL).title(title));
+
+    // Keep the selected match in view
+    let rows = chunks[1].height.saturating_sub(2) as usize;
+    let first = (fuzzy_finder.selected + 1).saturating_sub(rows);
+    let lines: Vec<Line> = fuzzy_finder
+        .matches
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(rows)
+        .map(|(index, m)| {
+            let matched = fuzzy_finder.matched_chars(&m.path);
+            let mut spans = vec![match index == fuzzy_finder.selected {
+                true => Span::styled("▶ ", Style::default().fg(THEME.selection_indicator_color)),
+                false => Span::raw("  "),
+            }];
+            spans.extend(m.path.chars().enumerate().map(|(i, c)| {
+                let style = match matched.contains(&i) {
+                    true => Style::default()
+                        .fg(THEME.name_color)
+                        .bg(THEME.search_indicator_color),
+                    false => Style::default().fg(THEME.name_color),
+                };
+                Span::styled(c.to_string(), style)
+            }));
+            spans.push(Span::styled(
+                format!("  {}", fuzzy_finder::value_preview(&items[m.line_number])),
+                Style::default().fg(Color::DarkGray),
+            ));
+            Line::from(spans)
+        })
+        .collect();
+    let list = Paragraph::new(lines).block(Block::default().borders(Borders::ALL));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(input, chunks[0]);
+    frame.render_widget(list, chunks[1]);
+    frame.set_cursor(
+        chunks[0].x + 1 + (fuzzy_finder.input.visual_cursor() as u16).min(width.saturating_sub(3)),
+        chunks[0].y + 1,
+    );
+}